@@ -0,0 +1,28 @@
+use raylib::prelude::*;
+use crate::config::RenderConfig;
+
+/// Draws `text` centered at the bottom of the frame over a translucent band.
+/// `alpha` (0.0 - 1.0) fades the whole caption.
+pub fn draw_caption(d: &mut RaylibDrawHandle, config: &RenderConfig, text: &str, alpha: f32) {
+    if text.is_empty() || alpha <= 0.0 {
+        return;
+    }
+    let alpha = alpha.min(1.0);
+
+    // 40px at 1080p, scaled with the output height
+    let font_size = (config.height as f32 / 27.0).round() as i32;
+    let padding = font_size / 2;
+
+    let text_width = d.measure_text(text, font_size);
+    let x = (config.width - text_width) / 2;
+    let y = config.height - font_size - padding * 3;
+
+    d.draw_rectangle(
+        x - padding,
+        y - padding,
+        text_width + padding * 2,
+        font_size + padding * 2,
+        Color::new(0, 0, 0, (160.0 * alpha) as u8),
+    );
+    d.draw_text(text, x, y, font_size, Color::new(255, 255, 255, (255.0 * alpha) as u8));
+}
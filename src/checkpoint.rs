@@ -0,0 +1,178 @@
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::cli::Settings;
+use crate::constants::CHECKPOINT_INTERVAL;
+
+/// Progress of a render written in segments, saved next to the output after each completed
+/// segment so that `--resume` can continue an interrupted render:
+///
+/// ```json
+/// {"render": "...", "seed": 1234, "frames": [0, 18000], "segment_frames": 1800, "completed": [1, 2, 5]}
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub render: String, // everything the frames depend on, see render_id()
+    pub seed: u64,
+    pub frames: [u64; 2],
+    pub segment_frames: u64,
+    pub completed: BTreeSet<usize>, // complete segments, in any order with --jobs
+}
+
+/// `<name>.checkpoint.json` next to the final video
+pub fn checkpoint_path(output: &Path) -> PathBuf {
+    let stem = output.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    output.with_file_name(format!("{}.checkpoint.json", stem))
+}
+
+/// Describes the settings that change the frames, a render can only be resumed with the same ones
+pub fn render_id(settings: &Settings) -> String {
+    format!(
+        "{} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?}",
+        settings.engine,
+        settings.engine_options,
+        settings.sources,
+        settings.scan,
+        settings.sort,
+        settings.clock_offsets,
+        settings.config,
+        settings.encoder,
+        settings.slides,
+    )
+}
+
+impl Checkpoint {
+    pub fn new(settings: &Settings, frames: Range<u64>, segment_frames: u64) -> Self {
+        Self {
+            render: render_id(settings),
+            seed: settings.config.seed,
+            frames: [frames.start, frames.end],
+            segment_frames: segment_frames.max(1),
+            completed: BTreeSet::new(),
+        }
+    }
+
+    /// Checkpoint of the render resumed with --resume, or of a new render of `frames`
+    pub fn resume_or_new(settings: &Settings, frames: &Range<u64>) -> Result<Self, String> {
+        match &settings.resume {
+            Some(checkpoint) => {
+                checkpoint.check(settings, frames)?;
+                Ok(checkpoint.clone())
+            }
+            None => Ok(Self::new(settings, frames.clone(), settings.config.frame_count(CHECKPOINT_INTERVAL))),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot resume: failed to read checkpoint {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Cannot resume: invalid checkpoint {}: {}", path.display(), e))
+    }
+
+    /// Written to a temporary file first, a crash never leaves a truncated checkpoint
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut temporary = path.as_os_str().to_os_string();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(&temporary, content)
+            .with_context(|| format!("Failed to write checkpoint {}", temporary.display()))?;
+        std::fs::rename(&temporary, path)
+            .with_context(|| format!("Failed to write checkpoint {}", path.display()))
+    }
+
+    /// Checks that the checkpoint was written by the same render
+    pub fn check(&self, settings: &Settings, frames: &Range<u64>) -> Result<(), String> {
+        if self.render != render_id(settings) {
+            return Err("Cannot resume: the settings changed since the render was interrupted".to_string());
+        }
+        if self.frames != [frames.start, frames.end] {
+            return Err(format!(
+                "Cannot resume: the interrupted render was frames {} to {}, not {} to {}",
+                self.frames[0], self.frames[1], frames.start, frames.end
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn segment_count(&self) -> usize {
+        (self.frames[1] - self.frames[0]).div_ceil(self.segment_frames) as usize
+    }
+
+    /// Segments left to render, in order
+    pub fn pending(&self) -> Vec<usize> {
+        (1..=self.segment_count()).filter(|index| !self.completed.contains(index)).collect()
+    }
+
+    /// Number of complete segments at the start of the video, the part that can be played
+    pub fn complete_prefix(&self) -> usize {
+        (1..=self.segment_count()).take_while(|index| self.completed.contains(index)).count()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete_prefix() == self.segment_count()
+    }
+
+    /// Frames of segment `index` (from 1)
+    pub fn segment(&self, index: usize) -> Range<u64> {
+        let start = self.frames[0] + (index as u64 - 1) * self.segment_frames;
+        start..(start + self.segment_frames).min(self.frames[1])
+    }
+
+    /// Index and frames of the segments `indexes`
+    pub fn segments(&self, indexes: impl IntoIterator<Item = usize>) -> Vec<(usize, Range<u64>)> {
+        indexes.into_iter().map(|index| (index, self.segment(index))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint(completed: &[usize]) -> Checkpoint {
+        Checkpoint {
+            render: String::new(),
+            seed: 1,
+            frames: [0, 95],
+            segment_frames: 20,
+            completed: completed.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn the_last_segment_is_shorter() {
+        let checkpoint = checkpoint(&[]);
+        assert_eq!(checkpoint.segment_count(), 5);
+        assert_eq!(checkpoint.segment(1), 0..20);
+        assert_eq!(checkpoint.segment(5), 80..95);
+    }
+
+    #[test]
+    fn segments_complete_out_of_order_leave_gaps() {
+        let checkpoint = checkpoint(&[1, 2, 4]);
+        assert_eq!(checkpoint.pending(), vec![3, 5]);
+        assert_eq!(checkpoint.complete_prefix(), 2);
+        assert!(!checkpoint.is_complete());
+    }
+
+    #[test]
+    fn a_render_is_complete_with_every_segment() {
+        let checkpoint = checkpoint(&[5, 3, 1, 2, 4]);
+        assert!(checkpoint.pending().is_empty());
+        assert_eq!(checkpoint.complete_prefix(), 5);
+        assert!(checkpoint.is_complete());
+    }
+
+    #[test]
+    fn completed_segments_are_saved_and_loaded() {
+        let path = std::env::temp_dir().join(format!("slideshow-test-{}-checkpoint.json", std::process::id()));
+        checkpoint(&[1, 4]).save(&path).unwrap();
+        let loaded = Checkpoint::load(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded.unwrap().completed, BTreeSet::from([1, 4]));
+    }
+}
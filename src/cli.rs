@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use clap::{ArgGroup, ArgMatches, CommandFactory, FromArgMatches, Parser};
+use crate::constants::*;
+use crate::config::RenderConfig;
+use crate::checkpoint::{checkpoint_path, Checkpoint};
+use crate::clock::ClockOffset;
+use crate::jobs::{parse_chunk, Chunk};
+use crate::encoder::{resolve_encoder, Container, EncoderOptions, EncoderOverrides};
+use crate::engine::{engine_names, find_engine, parse_engine, resolve_engine_options, EngineOptions, ENGINES};
+use crate::output::{check_overwrite, expand_template, DEFAULT_OUTPUT_TEMPLATE};
+use crate::progress::ProgressMode;
+use crate::project::Project;
+use crate::scan::ScanOptions;
+use crate::sequence::options::SequenceOptions;
+use crate::sort::{parse_sort_order, SortOrder};
+use crate::slide_settings::SlideSettings;
+use crate::timecode::{parse_range, parse_time, TimeRange};
+use crate::timing::{resolve_timing, TimingOverrides};
+
+#[derive(Debug, Parser)]
+#[command(version, about, long_about = None)]
+#[command(group(ArgGroup::new("selection").args(["frame_at", "range"])))]
+pub struct App {
+    #[arg(short, long, required_unless_present_any = ["project", "list_engines"], value_parser = parse_engine, help = "Engine to use (spiral, push-box or sequence, see --list-engines)")]
+    engine: Option<String>,
+    
+    #[arg(short, long, required_unless_present_any = ["project", "list_engines"], help = "Directory containing images (may be repeated)")]
+    directory: Vec<PathBuf>,
+
+    #[arg(short, long, help = "Also scan the subdirectories of the image directories")]
+    recursive: bool,
+
+    #[arg(long, value_name = "GLOB", help = "Only use the images matching this pattern, relative to their directory (may be repeated, e.g. '2019/**')")]
+    include: Vec<String>,
+
+    #[arg(long, value_name = "GLOB", help = "Skip the images matching this pattern, relative to their directory (may be repeated, e.g. '**/thumbnails/*')")]
+    exclude: Vec<String>,
+
+    #[arg(long, help = "Follow symbolic links to images and directories (they are skipped otherwise)")]
+    follow_symlinks: bool,
+
+    #[arg(long, value_name = "ORDER", value_parser = parse_sort_order, help = "Order of the photos: name, natural (IMG_2 before IMG_10), mtime, exif-date (capture date, else mtime) or random (seeded), optionally followed by ',reverse' [default: name]")]
+    sort: Option<SortOrder>,
+
+    #[arg(long, help = "List the engines with their description and options, then exit")]
+    pub list_engines: bool,
+
+    #[arg(short, long, value_name = "FILE", help = "TOML project file describing the render (command line options override its values)")]
+    project: Option<PathBuf>,
+
+    #[arg(short, long, value_name = "TEMPLATE", help = "Output video file, may use {dir}, {engine}, {date}, {ext}, {width}, {height} and {fps} [default: {dir}.{ext}]")]
+    output: Option<PathBuf>,
+
+    #[arg(long, help = "Overwrite the output video if it already exists")]
+    force: bool,
+
+    #[arg(long, help = "Output video width in pixels [default: 1920]")]
+    width: Option<i32>,
+
+    #[arg(long, help = "Output video height in pixels [default: 1080]")]
+    height: Option<i32>,
+
+    #[arg(long, help = "Output video frame rate [default: 60]")]
+    fps: Option<u32>,
+
+    #[arg(long, overrides_with = "seed", help = "Seed of the random layout, to render the exact same video again [default: random]")]
+    seed: Option<u64>,
+
+    #[arg(long, value_name = "FILE", help = "Audio track to mux into the video (cut to the video length, or followed by silence)")]
+    music: Option<PathBuf>,
+
+    #[arg(long, value_name = "TIME", value_parser = parse_time, requires = "png", help = "Render only the frame shown at TIME (83.5, 83.5s, MM:SS.s or HH:MM:SS.s)")]
+    frame_at: Option<f32>,
+
+    #[arg(long, value_name = "START..END", value_parser = parse_range, help = "Render only this part of the slideshow (e.g. 10s..20s, 1:30..), as a clip or PNG files with --png")]
+    range: Option<TimeRange>,
+
+    #[arg(long, value_name = "FILE", requires = "selection", help = "Write the selected frames as PNG files instead of a video ({frame} is replaced by the frame number)")]
+    png: Option<String>,
+
+    #[arg(long, conflicts_with_all = ["headless", "benchmark", "selection"], help = "Play the slideshow in real time in the window, without encoding (space: pause, right: step, R: restart, +/-: speed, digits + Enter: go to slide)")]
+    preview: bool,
+
+    #[arg(long, help = "Render offscreen only, without showing a window (works under Xvfb / software OpenGL)")]
+    headless: bool,
+
+    #[arg(long, conflicts_with_all = ["preview", "benchmark", "png"], help = "Continue an interrupted render from its complete segments (same options, the seed is read from the checkpoint)")]
+    resume: bool,
+
+    #[arg(short, long, default_value_t = 1, conflicts_with_all = ["preview", "benchmark", "selection"], help = "Render parts of the video in this many processes at once, then join them")]
+    jobs: usize,
+
+    // Set by --jobs for its worker processes
+    #[arg(long, hide = true, value_parser = parse_chunk)]
+    chunk: Option<Chunk>,
+
+    #[arg(long, value_name = "FRAMES", default_value_t = DEFAULT_PIPELINE_DEPTH, help = "Frames queued for encoding while the next ones are rendered (0 to encode on the render thread)")]
+    pipeline_depth: usize,
+
+    #[arg(long, conflicts_with = "selection", help = "Measure the rendering speed with and without the encoding pipeline and asynchronous readback, without writing a video")]
+    benchmark: bool,
+
+    #[arg(long, help = "Report progress as JSON lines on stdout instead of a progress bar")]
+    progress_json: bool,
+
+    #[arg(long, value_name = "FILE", help = "TOML file with per-phase durations (see --timing for presets)")]
+    timing_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    timing: TimingOverrides,
+
+    #[command(flatten)]
+    encoder: EncoderOverrides,
+
+    // Also holds the options each engine adds to the command
+    #[arg(skip)]
+    matches: ArgMatches,
+}
+
+/// Everything needed to run a render, merged from the project file and the command line.
+pub struct Settings {
+    pub engine: String,
+    pub engine_options: EngineOptions,
+    pub sources: Vec<PathBuf>,
+    pub scan: ScanOptions,
+    pub sort: SortOrder,
+    pub clock_offsets: Vec<ClockOffset>,
+    pub output: PathBuf,
+    pub force: bool, // the output file may be overwritten
+    pub encoder: EncoderOptions,
+    pub music: Option<PathBuf>,
+    pub config: RenderConfig,
+    pub slides: BTreeMap<PathBuf, SlideSettings>,
+    pub progress: ProgressMode,
+    pub frames: Option<Range<u64>>,
+    pub png: Option<String>,
+    pub preview: bool,
+    pub resume: Option<Checkpoint>,
+    pub jobs: usize,
+    pub chunk: Option<Chunk>,
+    pub headless: bool,
+    pub pipeline_depth: usize,
+    pub benchmark: bool,
+}
+
+impl App {
+    /// Parses the command line, with the options of every engine
+    pub fn parse_with_engines() -> App {
+        let command = ENGINES.iter().fold(App::command(), |command, engine| (engine.options)(command));
+        let matches = command.get_matches();
+        let mut app = App::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        app.matches = matches;
+        app
+    }
+
+    pub fn resolve(self) -> Result<Settings, String> {
+        let project = match &self.project {
+            Some(path) => Project::load(path)?,
+            None => Project::default(),
+        };
+
+        let engine = self.engine.or(project.engine)
+            .ok_or("No engine specified (use --engine or set 'engine' in the project file)")?;
+        if find_engine(&engine).is_none() {
+            return Err(format!("Unknown engine '{}' (expected one of: {}, see --list-engines)", engine, engine_names()));
+        }
+        let engine_options = resolve_engine_options(&engine, &project.engines, &project.base_dir, &self.matches)?;
+        if engine == "sequence" && engine_options.get::<SequenceOptions>().segments.is_empty() {
+            return Err("The sequence engine needs [[engines.sequence.segments]] in the project file".to_string());
+        }
+
+        let sources = if self.directory.is_empty() { project.sources } else { self.directory };
+        if sources.is_empty() {
+            return Err("No image source specified (use --directory or set 'sources' in the project file)".to_string());
+        }
+        let scan = ScanOptions::new(
+            self.recursive || project.recursive,
+            self.follow_symlinks || project.follow_symlinks,
+            if self.include.is_empty() { &project.include } else { &self.include },
+            if self.exclude.is_empty() { &project.exclude } else { &self.exclude },
+        )?;
+
+        let timing_file = self.timing_file.as_deref().map(TimingOverrides::load).transpose()?;
+        let timing_layers: Vec<&TimingOverrides> = project.timing.iter().chain(timing_file.iter()).collect();
+        let timing = resolve_timing(&timing_layers, &self.timing)?;
+
+        let mut config = RenderConfig::new(
+            self.width.or(project.width).unwrap_or(DEFAULT_RENDER_WIDTH),
+            self.height.or(project.height).unwrap_or(DEFAULT_RENDER_HEIGHT),
+            self.fps.or(project.fps).unwrap_or(DEFAULT_FPS),
+            timing,
+            self.seed.or(project.seed).unwrap_or_else(rand::random),
+        )?;
+
+        let output_template = self.output.or(project.output)
+            .unwrap_or(PathBuf::from(DEFAULT_OUTPUT_TEMPLATE));
+
+        // The extension of an explicit output file selects the container, unless set explicitly
+        let output_container = output_template.extension()
+            .and_then(|extension| Container::from_extension(&extension.to_string_lossy()));
+        let encoder = resolve_encoder(project.encoder.as_ref(), &self.encoder, output_container)?;
+
+        // Default output: <first source directory name>.<container> in the current directory
+        let dir_name = sources[0].canonicalize().unwrap_or(sources[0].clone())
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| format!("Cannot derive a video name from '{}'", sources[0].display()))?;
+        let output = expand_template(&output_template.to_string_lossy(), &[
+            ("dir", dir_name),
+            ("engine", engine.clone()),
+            ("date", crate::date::today()),
+            ("ext", encoder.container.extension().to_string()),
+            ("width", config.width.to_string()),
+            ("height", config.height.to_string()),
+            ("fps", config.fps.to_string()),
+        ])?;
+
+        // The seed of an interrupted render is only known from its checkpoint
+        let resume = if self.resume {
+            let checkpoint = Checkpoint::load(&checkpoint_path(&output))?;
+            if let Some(seed) = self.seed.or(project.seed).filter(|seed| *seed != checkpoint.seed) {
+                return Err(format!("Cannot resume with --seed {}: the interrupted render used seed {}", seed, checkpoint.seed));
+            }
+            config.seed = checkpoint.seed;
+            Some(checkpoint)
+        } else {
+            None
+        };
+
+        // Selected frames, the end is the end of the slideshow when not set
+        let frames = match (self.frame_at, self.range) {
+            (Some(time), _) => Some(config.frame_at(time)..config.frame_at(time) + 1),
+            (None, Some(range)) => Some(config.frame_at(range.start)..range.end.map_or(u64::MAX, |end| config.frame_at(end))),
+            (None, None) => None,
+        };
+        if let Some(png) = &self.png {
+            if self.range.is_some() && !png.contains("{frame}") {
+                return Err(format!("PNG file name '{}' must contain {{frame}} to write a range of frames", png));
+            }
+            expand_template(png, &[("frame", String::new())])?;
+        }
+
+        if self.jobs == 0 {
+            return Err("Invalid number of jobs: must be at least 1".to_string());
+        }
+        // Worker processes of --jobs write their segments, the output is written by their parent
+        let is_worker = self.chunk.is_some();
+
+        if !self.benchmark && !self.preview && self.png.is_none() && !is_worker {
+            check_overwrite(&output, self.force)?;
+        }
+
+        let music = self.music.or(project.music);
+        if let Some(music) = &music {
+            if !music.is_file() {
+                return Err(format!("Music file '{}' does not exist", music.display()));
+            }
+        }
+
+        Ok(Settings {
+            engine,
+            engine_options,
+            sources,
+            scan,
+            sort: self.sort.or(project.sort).unwrap_or_default(),
+            clock_offsets: project.clock_offsets,
+            output,
+            force: self.force,
+            encoder,
+            music,
+            config,
+            slides: project.slides,
+            // Workers report their progress to their parent
+            progress: if self.progress_json || is_worker { ProgressMode::Json } else { ProgressMode::Bar },
+            frames,
+            png: self.png,
+            preview: self.preview,
+            resume,
+            jobs: if is_worker { 1 } else { self.jobs },
+            chunk: self.chunk,
+            headless: self.headless || is_worker,
+            pipeline_depth: self.pipeline_depth,
+            benchmark: self.benchmark,
+        })
+    }
+}
@@ -0,0 +1,296 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use exif::{In, Reader, Tag, Value};
+use serde::Deserialize;
+use crate::date::{civil_from_days, unix_time};
+
+/// Correction of the clock of a camera, or of the photos of a directory, read from the
+/// `[[clock_offsets]]` tables of the project file:
+///
+/// ```toml
+/// [[clock_offsets]]
+/// make = "Apple"       # EXIF Make and/or Model of the camera
+/// model = "iPhone 12"
+/// offset = "+01:00"    # added to the capture times: this phone was an hour late
+///
+/// [[clock_offsets]]
+/// directory = "Dad"    # photos of this directory and its subdirectories
+/// offset = "-00:12:30"
+/// ```
+///
+/// The first matching entry applies.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClockOffset {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub directory: Option<PathBuf>, // canonical once loaded
+    pub offset: Offset,
+}
+
+/// Signed number of seconds, written `[+|-]HH:MM[:SS]`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Offset(pub i64);
+
+impl TryFrom<String> for Offset {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        parse_offset(&text).map(Offset)
+    }
+}
+
+/// Parses `[+|-]HH:MM[:SS]` into seconds
+pub fn parse_offset(text: &str) -> Result<i64, String> {
+    let error = || format!("Invalid clock offset '{}' (expected [+|-]HH:MM or [+|-]HH:MM:SS)", text);
+
+    let text = text.trim();
+    let (sign, rest) = match text.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let parts = rest.split(':')
+        .map(|part| part.parse::<u32>().map_err(|_| error()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (hours, minutes, seconds) = match parts[..] {
+        [hours, minutes] => (hours, minutes, 0),
+        [hours, minutes, seconds] => (hours, minutes, seconds),
+        _ => return Err(error()),
+    };
+    if minutes >= 60 || seconds >= 60 {
+        return Err(error());
+    }
+    Ok(sign * (hours as i64 * 3600 + minutes as i64 * 60 + seconds as i64))
+}
+
+impl ClockOffset {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.make.is_none() && self.model.is_none() && self.directory.is_none() {
+            return Err("an entry needs a make, a model or a directory".to_string());
+        }
+        Ok(())
+    }
+
+    /// Make and model are compared without case, entries with a make or a model never match
+    /// photos of an unknown camera
+    fn matches(&self, path: &Path, make: Option<&str>, model: Option<&str>) -> bool {
+        let same = |expected: &Option<String>, actual: Option<&str>| {
+            expected.as_ref().is_none_or(|expected| {
+                actual.is_some_and(|actual| actual.eq_ignore_ascii_case(expected.trim()))
+            })
+        };
+        same(&self.make, make)
+            && same(&self.model, model)
+            && self.directory.as_ref().is_none_or(|directory| {
+                path.canonicalize().is_ok_and(|path| path.starts_with(directory))
+            })
+    }
+}
+
+/// Correction (seconds) of the first entry matching a photo, 0 without any
+fn first_match(offsets: &[ClockOffset], path: &Path, make: Option<&str>, model: Option<&str>) -> i64 {
+    offsets.iter()
+        .find(|offset| offset.matches(path, make, model))
+        .map_or(0, |offset| offset.offset.0)
+}
+
+/// Correction (seconds) of the times of a photo without EXIF data, such as its modification
+/// time: only entries for its directory apply
+pub fn file_time_offset(path: &Path, offsets: &[ClockOffset]) -> i64 {
+    first_match(offsets, path, None, None)
+}
+
+/// Date and camera of a photo, from its EXIF data
+struct Capture {
+    local: i64,              // DateTimeOriginal, seconds since 1970-01-01 in the camera's time zone
+    utc_offset: Option<i64>, // OffsetTimeOriginal, seconds
+    make: Option<String>,
+    model: Option<String>,
+}
+
+fn ascii_field(exif: &exif::Exif, tag: Tag) -> Option<&[u8]> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values.first().map(|value| value.as_slice()),
+        _ => None,
+    }
+}
+
+fn text_field(exif: &exif::Exif, tag: Tag) -> Option<String> {
+    let text = String::from_utf8_lossy(ascii_field(exif, tag)?);
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Seconds since 1970-01-01 of an EXIF date and time, None for the `0000:00:00 00:00:00` of
+/// cameras whose clock was never set
+fn local_time(datetime: &exif::DateTime) -> Option<i64> {
+    if datetime.year == 0 || datetime.month == 0 || datetime.day == 0 {
+        return None;
+    }
+    Some(unix_time(
+        datetime.year as i64,
+        datetime.month as u32,
+        datetime.day as u32,
+        datetime.hour as u32,
+        datetime.minute as u32,
+        datetime.second as u32,
+    ))
+}
+
+impl Capture {
+    fn read(path: &Path) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        let exif = Reader::new().read_from_container(&mut BufReader::new(file)).ok()?;
+
+        let mut datetime = exif::DateTime::from_ascii(ascii_field(&exif, Tag::DateTimeOriginal)?).ok()?;
+        if let Some(offset) = ascii_field(&exif, Tag::OffsetTimeOriginal) {
+            let _ = datetime.parse_offset(offset);
+        }
+
+        Some(Self {
+            local: local_time(&datetime)?,
+            utc_offset: datetime.offset.map(|minutes| minutes as i64 * 60),
+            make: text_field(&exif, Tag::Make),
+            model: text_field(&exif, Tag::Model),
+        })
+    }
+
+    /// Reads the capture of a photo and corrects its time with the first matching offset
+    fn corrected(path: &Path, offsets: &[ClockOffset]) -> Option<Self> {
+        let mut capture = Self::read(path)?;
+        capture.local += first_match(offsets, path, capture.make.as_deref(), capture.model.as_deref());
+        Some(capture)
+    }
+}
+
+/// Offset (seconds) of the time zone of this computer from UTC at `time` (seconds since
+/// 1970-01-01 UTC)
+#[cfg(unix)]
+fn local_utc_offset(time: i64) -> i64 {
+    let time = time as libc::time_t;
+    // SAFETY: localtime_r() only writes to the tm it is given, which is plain data
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+/// The time zone is unknown, local times are taken as UTC
+#[cfg(not(unix))]
+fn local_utc_offset(_time: i64) -> i64 {
+    0
+}
+
+/// Seconds since 1970-01-01 UTC of a local time, in the time zone of this computer without
+/// `utc_offset`
+fn utc_time(local: i64, utc_offset: Option<i64>) -> i64 {
+    local - utc_offset.unwrap_or_else(|| local_utc_offset(local))
+}
+
+/// Capture time of a photo (seconds since 1970-01-01 UTC) from its EXIF DateTimeOriginal,
+/// corrected by the clock offsets. Without OffsetTimeOriginal the photo is taken to be shot in
+/// the time zone of this computer, like the modification times it is compared with are shown.
+pub fn capture_time(path: &Path, offsets: &[ClockOffset]) -> Option<i64> {
+    let capture = Capture::corrected(path, offsets)?;
+    Some(utc_time(capture.local, capture.utc_offset))
+}
+
+/// Capture date of a photo as YYYY-MM-DD, in the time zone of the camera and corrected by the
+/// clock offsets
+pub fn capture_date(path: &Path, offsets: &[ClockOffset]) -> Option<String> {
+    let capture = Capture::corrected(path, offsets)?;
+    let (year, month, day) = civil_from_days(capture.local.div_euclid(86400));
+    Some(format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(text: &str) -> exif::DateTime {
+        exif::DateTime::from_ascii(text.as_bytes()).unwrap()
+    }
+
+    fn offset(make: Option<&str>, model: Option<&str>, directory: Option<&Path>, offset: &str) -> ClockOffset {
+        ClockOffset {
+            make: make.map(str::to_string),
+            model: model.map(str::to_string),
+            directory: directory.map(Path::to_path_buf),
+            offset: Offset(parse_offset(offset).unwrap()),
+        }
+    }
+
+    #[test]
+    fn capture_times_without_time_zone_are_in_the_local_one() {
+        assert_eq!(utc_time(1_700_000_000, Some(3600)), 1_699_996_400);
+        assert_eq!(utc_time(1_700_000_000, Some(0)), 1_700_000_000);
+
+        // A modification time shown in local time goes back to the same time
+        let modified = 1_700_000_000;
+        assert_eq!(utc_time(modified + local_utc_offset(modified), None), modified);
+    }
+
+    #[test]
+    fn offsets_are_parsed() {
+        assert_eq!(parse_offset("-00:12:30"), Ok(-750));
+        assert_eq!(parse_offset("+1:00"), Ok(3600));
+        assert_eq!(parse_offset("01:00"), Ok(3600));
+        assert_eq!(parse_offset(" -25:00:01 "), Ok(-90001));
+    }
+
+    #[test]
+    fn invalid_offsets_are_rejected() {
+        for text in ["", "1", "+1", "1:60", "1:00:60", "1:2:3:4", "--1:00", "+-1:00", "1h", "1:-5", "1:00 ago"] {
+            assert!(parse_offset(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn the_first_matching_offset_applies() {
+        let offsets = [
+            offset(None, Some("pixel 7"), None, "+01:00"),
+            offset(Some("Google"), None, None, "+02:00"),
+            offset(Some("Google"), Some("Pixel 7"), None, "+03:00"),
+        ];
+        let path = Path::new("IMG_0001.jpg");
+        assert_eq!(first_match(&offsets, path, Some("Google"), Some("Pixel 7")), 3600);
+        assert_eq!(first_match(&offsets, path, Some("Google"), Some("Pixel 8")), 7200);
+        assert_eq!(first_match(&offsets, path, Some("Apple"), Some("iPhone 12")), 0);
+        assert_eq!(first_match(&offsets, path, None, None), 0);
+    }
+
+    #[test]
+    fn only_directory_offsets_apply_without_exif_data() {
+        let dir = std::env::temp_dir().join(format!("slideshow-test-{}-clock", std::process::id()));
+        std::fs::create_dir_all(dir.join("Dad")).unwrap();
+        let photo = dir.join("Dad").join("IMG_0001.jpg");
+        std::fs::write(&photo, b"").unwrap();
+        let dad = dir.join("Dad").canonicalize().unwrap();
+
+        let offsets = [
+            offset(Some("Canon"), None, None, "+01:00"),
+            offset(None, None, Some(&dad), "-00:12:30"),
+            offset(None, None, Some(&dad), "+02:00"),
+        ];
+        assert_eq!(file_time_offset(&photo, &offsets), -750);
+        assert_eq!(file_time_offset(&dir.join("IMG_0002.jpg"), &offsets), 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn capture_times_are_read_from_exif_dates() {
+        assert_eq!(local_time(&datetime("1970:01:01 00:00:00")), Some(0));
+        assert_eq!(local_time(&datetime("2024:02:29 12:30:15")), Some(1709209815));
+    }
+
+    #[test]
+    fn unset_camera_clocks_have_no_capture_time() {
+        assert_eq!(local_time(&datetime("0000:00:00 00:00:00")), None);
+        assert_eq!(local_time(&datetime("2024:00:10 12:00:00")), None);
+        assert_eq!(local_time(&datetime("2024:03:00 12:00:00")), None);
+    }
+}
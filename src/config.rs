@@ -0,0 +1,103 @@
+use crate::timing::Timing;
+
+/// Output settings shared by the main loop, the ffmpeg sink and every engine.
+#[derive(Debug, Clone)]
+pub struct RenderConfig {
+    pub width: i32,
+    pub height: i32,
+    pub fps: u32,
+    pub timing: Timing,
+    pub seed: u64, // seeds every random choice of the engines, for reproducible renders
+}
+
+impl RenderConfig {
+    pub fn new(width: i32, height: i32, fps: u32, timing: Timing, seed: u64) -> Result<Self, String> {
+        // yuv420p (and most codecs) require even dimensions
+        if width <= 0 || height <= 0 || width % 2 != 0 || height % 2 != 0 {
+            return Err(format!("Invalid resolution {}x{}: width and height must be positive even numbers", width, height));
+        }
+        if fps == 0 {
+            return Err("Invalid frame rate: fps must be greater than 0".to_string());
+        }
+        Ok(Self { width, height, fps, timing, seed })
+    }
+
+    /// Time per frame (seconds)
+    pub fn frame_time(&self) -> f32 {
+        1.0 / self.fps as f32
+    }
+
+    /// Number of frames needed to show `duration` seconds
+    pub fn frame_count(&self, duration: f32) -> u64 {
+        (duration * self.fps as f32).ceil().max(0.0) as u64
+    }
+
+    /// Time of the slideshow shown by frame `frame`
+    pub fn frame_timestamp(&self, frame: u64) -> f32 {
+        (frame as f64 / self.fps as f64) as f32
+    }
+
+    /// Index of the frame on screen `time` seconds into the video
+    pub fn frame_at(&self, time: f32) -> u64 {
+        // Tolerance for times that are exact multiples of the frame time, like 0.7s at 30 fps
+        (time * self.fps as f32 + 1e-3).floor().max(0.0) as u64
+    }
+
+    /// Width / height of the output (> 1.0 for landscape, < 1.0 for portrait)
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+
+    /// Scale factor that fits a `width` x `height` image inside `margin` (0.0 - 1.0) of the
+    /// output on both axes. Images are never upscaled.
+    pub fn fit_scale(&self, width: f32, height: f32, margin: f32) -> f32 {
+        let scale_x = self.width as f32 * margin / width;
+        let scale_y = self.height as f32 * margin / height;
+        scale_x.min(scale_y).min(1.0)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(width: i32, height: i32) -> RenderConfig {
+        RenderConfig::new(width, height, 30, Timing::default(), 0).unwrap()
+    }
+
+    #[test]
+    fn even_positive_sizes_and_frame_rates_are_accepted() {
+        let config = RenderConfig::new(1080, 1920, 24, Timing::default(), 7).unwrap();
+        assert_eq!((config.width, config.height, config.fps, config.seed), (1080, 1920, 24, 7));
+    }
+
+    #[test]
+    fn odd_or_empty_sizes_are_rejected() {
+        for (width, height) in [(1921, 1080), (1920, 1081), (0, 1080), (1920, 0), (-1920, 1080), (1920, -2)] {
+            assert!(RenderConfig::new(width, height, 30, Timing::default(), 0).is_err(), "{}x{}", width, height);
+        }
+    }
+
+    #[test]
+    fn a_zero_frame_rate_is_rejected() {
+        assert!(RenderConfig::new(1920, 1080, 0, Timing::default(), 0).is_err());
+    }
+
+    #[test]
+    fn images_are_fitted_inside_the_margin() {
+        let config = output(1920, 1080);
+        // Limited by the height for a square image, by the width for a panorama
+        assert_eq!(config.fit_scale(2160.0, 2160.0, 0.5), 0.25);
+        assert_eq!(config.fit_scale(7680.0, 1080.0, 1.0), 0.25);
+        // Portrait output: limited by the width
+        assert_eq!(output(1080, 1920).fit_scale(2160.0, 2160.0, 1.0), 0.5);
+    }
+
+    #[test]
+    fn images_are_never_upscaled() {
+        let config = output(1920, 1080);
+        assert_eq!(config.fit_scale(640.0, 480.0, 0.9), 1.0);
+        assert_eq!(config.fit_scale(1920.0, 1080.0, 1.0), 1.0);
+    }
+}
@@ -1,8 +1,15 @@
-pub const RENDER_WIDTH: i32 = 1920;           // Width of the render texture
-pub const RENDER_HEIGHT: i32 = 1080;          // Height of the render texture
-pub const FPS: u32 = 60;                      // Frames per second
-pub const FRAME_TIME: f32 = 1.0 / FPS as f32; // Time per frame (seconds)
+pub const DEFAULT_RENDER_WIDTH: i32 = 1920;   // Default width of the render texture
+pub const DEFAULT_RENDER_HEIGHT: i32 = 1080;  // Default height of the render texture
+pub const DEFAULT_FPS: u32 = 60;              // Default frames per second
+pub const PREVIEW_FPS: u32 = 30;              // Refresh rate of the preview window while encoding
+pub const DEFAULT_PIPELINE_DEPTH: usize = 3;  // Default number of frames queued for the ffmpeg writer thread
+pub const READBACK_BUFFERS: usize = 3;        // Frames being copied from the GPU while the next ones are rendered
+pub const BENCHMARK_DURATION: f32 = 10.0;     // Length of video rendered by each --benchmark pass (seconds)
+pub const CHECKPOINT_INTERVAL: f32 = 30.0;    // Length of video encoded to each resumable segment (seconds)
 
-pub const ANIMATION_DURATION: f32 = 0.5;      // Duration for background animation (seconds)
-pub const DISPLAY_DURATION: f32 = 2.0;        // Duration each slide is shown prominently (seconds)
-pub const CLEANUP_INTERVAL: f32 = 0.2;        // Time between background slides disappearing (seconds)
+pub const DEFAULT_ANIMATION_DURATION: f32 = 0.5; // Default duration for background animation (seconds)
+pub const DEFAULT_DISPLAY_DURATION: f32 = 2.0;   // Default duration each slide is shown prominently (seconds)
+pub const DEFAULT_CLEANUP_INTERVAL: f32 = 0.2;   // Default time between background slides disappearing (seconds)
+pub const DEFAULT_SEGMENT_TRANSITION: f32 = 1.0; // Default duration of a transition between sequence segments (seconds)
+
+pub const HERO_DURATION_FACTOR: f32 = 2.0;       // "hero" slides are displayed this many times longer
@@ -0,0 +1,75 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// (year, month, day) of a number of days since 1970-01-01 (proleptic Gregorian calendar)
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Number of days since 1970-01-01 of a date (proleptic Gregorian calendar), the inverse of
+/// civil_from_days. See http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12; // months from March
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Seconds since 1970-01-01 00:00:00 of a date and time
+pub fn unix_time(year: i64, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> i64 {
+    days_from_civil(year, month, day) * 86400 + (hour * 3600 + minute * 60 + second) as i64
+}
+
+/// Today's date (UTC) as YYYY-MM-DD
+pub fn today() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_are_converted_to_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(59), (1970, 3, 1));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19723), (2024, 1, 1));
+        assert_eq!(civil_from_days(19782), (2024, 2, 29));
+        assert_eq!(civil_from_days(-719468), (0, 3, 1));
+    }
+
+    #[test]
+    fn dates_are_converted_to_days_and_back() {
+        for days in [-719468, -1, 0, 59, 11016, 19723, 19782, 2932896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days, "{}-{}-{}", year, month, day);
+        }
+        assert_eq!(days_from_civil(2000, 3, 1) - days_from_civil(2000, 2, 28), 2);
+        assert_eq!(days_from_civil(1900, 3, 1) - days_from_civil(1900, 2, 28), 1);
+    }
+
+    #[test]
+    fn date_times_are_converted_to_unix_times() {
+        assert_eq!(unix_time(1970, 1, 1, 0, 0, 0), 0);
+        assert_eq!(unix_time(1969, 12, 31, 23, 59, 59), -1);
+        assert_eq!(unix_time(2024, 2, 29, 12, 30, 15), 1709209815);
+        let time = unix_time(2023, 12, 31, 23, 0, 0);
+        assert_eq!(civil_from_days(time.div_euclid(86400)), (2023, 12, 31));
+    }
+}
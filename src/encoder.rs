@@ -0,0 +1,259 @@
+use std::path::PathBuf;
+use serde::Deserialize;
+
+pub const ENCODER_PRESETS: &[&str] = &["default", "web", "web-av1", "archive", "edit"];
+
+/// Video encoding settings of the ffmpeg sink
+#[derive(Debug, Clone)]
+pub struct EncoderOptions {
+    pub codec: String,
+    pub pix_fmt: String,
+    pub crf: Option<u32>,
+    pub speed: Option<String>,   // ffmpeg -preset
+    pub bitrate: Option<String>, // e.g. "8M"
+    pub profile: Option<String>, // ffmpeg -profile:v
+    pub container: Container,
+    pub ffmpeg_program: PathBuf, // ffmpeg found in PATH unless set
+}
+
+const FFMPEG_PROGRAM: &str = "ffmpeg";
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Container {
+    Mp4,
+    Mkv,
+    Webm,
+    Mov,
+}
+
+impl Container {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "mp4" | "m4v" => Some(Container::Mp4),
+            "mkv" => Some(Container::Mkv),
+            "webm" => Some(Container::Webm),
+            "mov" => Some(Container::Mov),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mkv => "mkv",
+            Container::Webm => "webm",
+            Container::Mov => "mov",
+        }
+    }
+
+    /// ffmpeg muxer name (-f)
+    pub fn muxer(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mkv => "matroska",
+            Container::Webm => "webm",
+            Container::Mov => "mov",
+        }
+    }
+}
+
+impl EncoderOptions {
+    pub fn preset(name: &str) -> Result<Self, String> {
+        match name {
+            // H.264, plays everywhere
+            "default" => Ok(Self {
+                codec: "libx264".to_string(),
+                pix_fmt: "yuv420p".to_string(),
+                crf: None,
+                speed: None,
+                bitrate: None,
+                profile: None,
+                container: Container::Mp4,
+                ffmpeg_program: PathBuf::from(FFMPEG_PROGRAM),
+            }),
+            // VP9 constant quality for the web
+            "web" => Ok(Self {
+                codec: "libvpx-vp9".to_string(),
+                pix_fmt: "yuv420p".to_string(),
+                crf: Some(32),
+                speed: None,
+                bitrate: Some("0".to_string()),
+                profile: None,
+                container: Container::Webm,
+                ffmpeg_program: PathBuf::from(FFMPEG_PROGRAM),
+            }),
+            // AV1 for the web, smaller files, slower to encode
+            "web-av1" => Ok(Self {
+                codec: "libsvtav1".to_string(),
+                pix_fmt: "yuv420p".to_string(),
+                crf: Some(35),
+                speed: Some("8".to_string()),
+                bitrate: None,
+                profile: None,
+                container: Container::Webm,
+                ffmpeg_program: PathBuf::from(FFMPEG_PROGRAM),
+            }),
+            // H.265 10 bits, high quality
+            "archive" => Ok(Self {
+                codec: "libx265".to_string(),
+                pix_fmt: "yuv420p10le".to_string(),
+                crf: Some(18),
+                speed: Some("slow".to_string()),
+                bitrate: None,
+                profile: None,
+                container: Container::Mkv,
+                ffmpeg_program: PathBuf::from(FFMPEG_PROGRAM),
+            }),
+            // ProRes 422 HQ for editing software
+            "edit" => Ok(Self {
+                codec: "prores_ks".to_string(),
+                pix_fmt: "yuv422p10le".to_string(),
+                crf: None,
+                speed: None,
+                bitrate: None,
+                profile: Some("3".to_string()),
+                container: Container::Mov,
+                ffmpeg_program: PathBuf::from(FFMPEG_PROGRAM),
+            }),
+            _ => Err(format!("Unknown encoder preset '{}' (expected one of: {})", name, ENCODER_PRESETS.join(", "))),
+        }
+    }
+
+    /// Applies the values set in `overrides` on top of these options
+    pub fn apply(&mut self, overrides: &EncoderOverrides) {
+        if let Some(codec) = &overrides.codec {
+            self.codec = codec.clone();
+        }
+        if let Some(pix_fmt) = &overrides.pix_fmt {
+            self.pix_fmt = pix_fmt.clone();
+        }
+        if overrides.crf.is_some() {
+            self.crf = overrides.crf;
+        }
+        if overrides.speed.is_some() {
+            self.speed = overrides.speed.clone();
+        }
+        if overrides.bitrate.is_some() {
+            self.bitrate = overrides.bitrate.clone();
+        }
+        if overrides.profile.is_some() {
+            self.profile = overrides.profile.clone();
+        }
+        if let Some(container) = overrides.container {
+            self.container = container;
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.codec.trim().is_empty() {
+            return Err("codec: must not be empty".to_string());
+        }
+        if self.pix_fmt.trim().is_empty() {
+            return Err("pix_fmt: must not be empty".to_string());
+        }
+        if self.container == Container::Webm
+            && !["libvpx", "libvpx-vp9", "libaom-av1", "libsvtav1", "librav1e"].contains(&self.codec.as_str())
+        {
+            return Err(format!("codec '{}' cannot be stored in a WebM container (use VP8, VP9 or AV1)", self.codec));
+        }
+        Ok(())
+    }
+
+    /// Audio codec matching the container, for the music track
+    pub fn audio_codec(&self) -> &'static str {
+        match self.container {
+            Container::Webm => "libopus",
+            Container::Mov if self.codec.starts_with("prores") => "pcm_s16le",
+            _ => "aac",
+        }
+    }
+
+    /// Video encoding options, without the container specific ones
+    pub fn codec_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "-c:v".to_string(), self.codec.clone(),
+            "-pix_fmt".to_string(), self.pix_fmt.clone(),
+        ];
+        if let Some(crf) = self.crf {
+            args.extend(["-crf".to_string(), crf.to_string()]);
+        }
+        if let Some(speed) = &self.speed {
+            args.extend(["-preset".to_string(), speed.clone()]);
+        }
+        if let Some(bitrate) = &self.bitrate {
+            args.extend(["-b:v".to_string(), bitrate.clone()]);
+        }
+        if let Some(profile) = &self.profile {
+            args.extend(["-profile:v".to_string(), profile.clone()]);
+        }
+        args
+    }
+
+    /// Container options, also used when the video stream is copied
+    pub fn container_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        // Let Apple players recognize H.265 streams
+        if self.codec == "libx265" && matches!(self.container, Container::Mp4 | Container::Mov) {
+            args.extend(["-tag:v".to_string(), "hvc1".to_string()]);
+        }
+        if matches!(self.container, Container::Mp4 | Container::Mov) {
+            args.extend(["-movflags".to_string(), "+faststart".to_string()]);
+        }
+        args.extend(["-f".to_string(), self.container.muxer().to_string()]);
+        args
+    }
+}
+
+// Partial encoder settings, as read from the command line or the [encoder] table of the project file.
+#[derive(Debug, Default, Clone, Deserialize, clap::Args)]
+#[command(next_help_heading = "Encoding")]
+#[serde(deny_unknown_fields)]
+pub struct EncoderOverrides {
+    #[arg(id = "encoder_preset", long = "encoder", value_name = "PRESET", help = "Encoder preset: default (H.264 MP4), web (VP9 WebM), web-av1 (AV1 WebM), archive (H.265 MKV) or edit (ProRes MOV)")]
+    pub preset: Option<String>,
+
+    #[arg(long, help = "ffmpeg video codec (libx264, libx265, libvpx-vp9, libsvtav1, prores_ks...)")]
+    pub codec: Option<String>,
+
+    #[arg(long, value_name = "FORMAT", help = "ffmpeg output pixel format (yuv420p, yuv420p10le...)")]
+    pub pix_fmt: Option<String>,
+
+    #[arg(long, help = "Constant rate factor (lower is better quality)")]
+    pub crf: Option<u32>,
+
+    #[arg(long = "codec-preset", value_name = "PRESET", help = "ffmpeg encoding speed preset (ultrafast ... veryslow)")]
+    pub speed: Option<String>,
+
+    #[arg(long, help = "Target video bitrate (e.g. 8M)")]
+    pub bitrate: Option<String>,
+
+    #[arg(long, help = "ffmpeg codec profile (e.g. 3 for ProRes 422 HQ)")]
+    pub profile: Option<String>,
+
+    #[arg(long, value_enum, help = "Output container [default: from the output file extension, else from the encoder preset]")]
+    pub container: Option<Container>,
+}
+
+/// Builds the final encoder options: preset, then project file, then command line values.
+/// `output_container` (from the output file extension) is used when no container is set.
+pub fn resolve_encoder(file: Option<&EncoderOverrides>, cli: &EncoderOverrides, output_container: Option<Container>) -> Result<EncoderOptions, String> {
+    let default_preset = match output_container {
+        Some(Container::Webm) => "web",
+        _ => "default",
+    };
+    let preset = cli.preset.as_deref()
+        .or(file.and_then(|f| f.preset.as_deref()))
+        .unwrap_or(default_preset);
+
+    let mut options = EncoderOptions::preset(preset)?;
+    if let Some(container) = output_container {
+        options.container = container;
+    }
+    if let Some(file) = file {
+        options.apply(file);
+    }
+    options.apply(cli);
+    options.validate()?;
+    Ok(options)
+}
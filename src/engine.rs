@@ -1,8 +1,225 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::Arc;
+use clap::parser::ValueSource;
+use clap::FromArgMatches;
 use raylib::prelude::*;
-use std::path::PathBuf;
+use serde::de::DeserializeOwned;
+use crate::config::RenderConfig;
+use crate::slide_settings::SlideSource;
+use crate::spiral::engine::SpiralEngine;
+use crate::push_box::engine::PushBoxEngine;
+use crate::sequence::engine::SequenceEngine;
 
+/// Engine name, its project file table, project file directory, command line
+pub type ResolveOptionsFn = fn(&str, Option<&toml::Table>, &Path, &clap::ArgMatches) -> Result<Arc<dyn AnyOptions>, String>;
+
+/// What an engine declares about itself, to be selected by name, listed by --list-engines and
+/// given its options
+pub struct EngineInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Adds the engine's own options to a command, to parse and list them
+    pub options: fn(clap::Command) -> clap::Command,
+    /// Resolves the engine's options, `resolve_options::<TheEngineOptions>`
+    pub resolve_options: ResolveOptionsFn,
+    /// Engines whose options it uses besides its own, from its resolved options
+    pub uses: fn(&EngineOptions) -> Vec<&'static str>,
+    pub create: fn(RenderConfig, &EngineOptions) -> Box<dyn Engine>,
+}
+
+pub const ENGINES: &[EngineInfo] = &[SpiralEngine::INFO, PushBoxEngine::INFO, SequenceEngine::INFO];
+
+pub fn find_engine(name: &str) -> Option<&'static EngineInfo> {
+    ENGINES.iter().find(|engine| engine.name == name)
+}
+
+/// Engine names for error messages: "spiral, push-box"
+pub fn engine_names() -> String {
+    ENGINES.iter().map(|engine| engine.name).collect::<Vec<_>>().join(", ")
+}
+
+/// clap value parser of --engine
+pub fn parse_engine(name: &str) -> Result<String, String> {
+    match find_engine(name) {
+        Some(engine) => Ok(engine.name.to_string()),
+        None => Err(format!("unknown engine (expected one of: {}, see --list-engines)", engine_names())),
+    }
+}
+
+/// Options of one engine, with their defaults
+pub trait EngineOptionSet: Default + Debug + Send + Sync + 'static {
+    /// Values set on the command line (the arguments added by `EngineInfo::options`) or in the
+    /// `[engines.<name>]` table of a project file
+    type Overrides: DeserializeOwned + FromArgMatches;
+
+    fn apply(&mut self, overrides: &Self::Overrides);
+
+    fn validate(&self) -> Result<(), String>;
+
+    /// Resolves the relative paths of a project file table against the project directory
+    fn resolve_paths(_overrides: &mut Self::Overrides, _base_dir: &Path) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Options of an engine whatever their type, `EngineOptions::get` gives them back
+pub trait AnyOptions: Any + Debug + Send + Sync {}
+
+impl<T: Any + Debug + Send + Sync> AnyOptions for T {}
+
+/// Builds the options of engine `name`: defaults, then project file table, then command line
+/// values
+pub fn resolve_options<O: EngineOptionSet>(name: &str, table: Option<&toml::Table>, base_dir: &Path, cli: &clap::ArgMatches) -> Result<Arc<dyn AnyOptions>, String> {
+    let mut options = O::default();
+    if let Some(table) = table {
+        let mut overrides: O::Overrides = table.clone().try_into()
+            .map_err(|e: toml::de::Error| format!("Invalid [engines.{}] in the project file: {}", name, e.message()))?;
+        O::resolve_paths(&mut overrides, base_dir)?;
+        options.apply(&overrides);
+    }
+    options.apply(&O::Overrides::from_arg_matches(cli).map_err(|e| e.to_string())?);
+    options.validate()?;
+    Ok(Arc::new(options))
+}
+
+/// Options of every engine, each engine reads its own
+#[derive(Debug, Clone, Default)]
+pub struct EngineOptions {
+    values: BTreeMap<&'static str, Arc<dyn AnyOptions>>,
+}
+
+impl EngineOptions {
+    pub fn get<O: EngineOptionSet>(&self) -> &O {
+        self.values.values()
+            .find_map(|value| (value.as_ref() as &dyn Any).downcast_ref::<O>())
+            .expect("the options of every engine are resolved")
+    }
+}
+
+/// Builds the options of every engine from the `[engines.<name>]` tables of the project file
+/// (paths relative to `base_dir`) and the command line.
+///
+/// Options of engines that `engine` does not use are mistakes: rejected on the command line,
+/// ignored with a warning in the project file, which may be shared between engines.
+pub fn resolve_engine_options(engine: &str, tables: &BTreeMap<String, toml::Table>, base_dir: &Path, cli: &clap::ArgMatches) -> Result<EngineOptions, String> {
+    let mut options = EngineOptions::default();
+    for info in ENGINES {
+        let value = (info.resolve_options)(info.name, tables.get(info.name), base_dir, cli)?;
+        options.values.insert(info.name, value);
+    }
+
+    let selected = find_engine(engine).ok_or_else(|| format!("Unknown engine '{}'", engine))?;
+    let mut used = vec![selected.name];
+    used.extend((selected.uses)(&options));
+    for info in ENGINES.iter().filter(|info| !used.contains(&info.name)) {
+        let command = (info.options)(clap::Command::new(info.name));
+        let option = command.get_arguments()
+            .find(|option| cli.value_source(option.get_id().as_str()) == Some(ValueSource::CommandLine));
+        if let Some(option) = option {
+            return Err(format!("--{} is an option of the {} engine, which the {} engine does not use",
+                option.get_long().unwrap_or(option.get_id().as_str()), info.name, selected.name));
+        }
+        if tables.contains_key(info.name) {
+            eprintln!("Warning: [engines.{}] of the project file is ignored, the {} engine does not use it", info.name, selected.name);
+        }
+    }
+    Ok(options)
+}
+
+/// Prints the engines with their description and options, for --list-engines
+pub fn print_engines() {
+    for engine in ENGINES {
+        println!("{}", engine.name);
+        println!("    {}", engine.description);
+
+        let command = (engine.options)(clap::Command::new(engine.name));
+        let options: Vec<(String, String)> = command.get_arguments()
+            .filter_map(|option| {
+                let long = option.get_long()?;
+                let choices: Vec<String> = option.get_possible_values().iter().map(|value| value.get_name().to_string()).collect();
+                let value = if choices.is_empty() {
+                    option.get_value_names().and_then(|names| names.first()).map_or("VALUE".to_string(), |name| name.to_string())
+                } else {
+                    choices.join("|")
+                };
+                let help = option.get_help().map(|help| help.to_string()).unwrap_or_default();
+                Some((format!("--{} <{}>", long, value), help))
+            })
+            .collect();
+        let width = options.iter().map(|(usage, _)| usage.len()).max().unwrap_or(0);
+        for (usage, help) in options {
+            println!("    {:width$}  {}", usage, help, width = width);
+        }
+        println!();
+    }
+}
+
+/// A slideshow laid out on a timeline once initialized: any frame can be rendered from its
+/// time alone, in any order, which allows seeking and rendering parts of the video.
 pub trait Engine {
-    fn new() -> Self where Self: Sized;
-    fn initialize(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread,paths: Vec<PathBuf>) -> bool;
-    fn render_frame(&mut self, dt: f32, rl: &mut RaylibHandle, thread: &RaylibThread, framebuffer: &mut RenderTexture2D) -> bool;
+    fn initialize(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, sources: Vec<SlideSource>) -> bool;
+    /// Length of the slideshow (seconds), known once initialized
+    fn total_duration(&self) -> f32;
+    fn slide_count(&self) -> usize;
+    /// Time slide `index` shows up
+    fn slide_start(&self, index: usize) -> f32;
+    /// Index of the slide shown at `time` and the name of its animation phase, for the preview HUD
+    fn slide_at(&self, time: f32) -> (usize, String);
+    /// Draws the slideshow as it is `time` seconds in
+    fn render_at(&self, time: f32, rl: &mut RaylibHandle, thread: &RaylibThread, framebuffer: &mut RenderTexture2D);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spiral::options::SpiralOptions;
+
+    fn matches(args: &[&str]) -> clap::ArgMatches {
+        ENGINES.iter()
+            .fold(clap::Command::new("slideshow"), |command, engine| (engine.options)(command))
+            .try_get_matches_from(std::iter::once("slideshow").chain(args.iter().copied()))
+            .unwrap()
+    }
+
+    fn tables(toml: &str) -> BTreeMap<String, toml::Table> {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn command_line_overrides_the_project_file() {
+        let tables = tables("[spiral]\nmax_rotation = 20.0\njitter = 0.02");
+        let options = resolve_engine_options("spiral", &tables, Path::new(""), &matches(&["--spiral-max-rotation", "5"])).unwrap();
+        assert_eq!(options.get::<SpiralOptions>().max_rotation, 5.0);
+        assert_eq!(options.get::<SpiralOptions>().jitter, 0.02);
+    }
+
+    #[test]
+    fn options_of_an_unused_engine_are_rejected() {
+        let error = resolve_engine_options("push-box", &BTreeMap::new(), Path::new(""), &matches(&["--spiral-max-rotation", "5"])).unwrap_err();
+        assert!(error.contains("--spiral-max-rotation"), "{}", error);
+
+        // Project files may be shared between engines
+        let tables = tables("[spiral]\nmax_rotation = 20.0");
+        assert!(resolve_engine_options("push-box", &tables, Path::new(""), &matches(&[])).is_ok());
+    }
+
+    #[test]
+    fn sequences_use_the_options_of_their_segments_engines() {
+        let tables = tables("[[sequence.segments]]\nengine = \"spiral\"");
+        let options = resolve_engine_options("sequence", &tables, Path::new(""), &matches(&["--spiral-jitter", "0.1"])).unwrap();
+        assert_eq!(options.get::<SpiralOptions>().jitter, 0.1);
+
+        let error = resolve_engine_options("sequence", &tables, Path::new(""), &matches(&["--push-box-margin", "0.5"])).unwrap_err();
+        assert!(error.contains("--push-box-margin"), "{}", error);
+    }
+
+    #[test]
+    fn invalid_project_file_tables_are_reported() {
+        let error = resolve_engine_options("spiral", &tables("[spiral]\nspin = 1"), Path::new(""), &matches(&[])).unwrap_err();
+        assert!(error.contains("[engines.spiral]"), "{}", error);
+        assert!(error.contains("spin"), "{}", error);
+    }
 }
@@ -1,57 +1,387 @@
-use std::process::{Command, Stdio};
-use std::io::Write;
-use raylib::prelude::*;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::io::{ErrorKind, Write};
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use anyhow::{anyhow, bail, Context, Result};
+use crate::config::RenderConfig;
+use crate::encoder::EncoderOptions;
+
+/// Number of lines of the ffmpeg log quoted in error messages
+const LOG_TAIL_LINES: usize = 10;
 
 pub struct Ffmpeg {
-    process: std::process::Child,
-    stdin: Option<std::process::ChildStdin>,   
+    process: Child,
+    stdin: Option<ChildStdin>,
+    log_path: PathBuf,
+}
+
+/// ffmpeg log of a video: `<video>.ffmpeg.log`
+pub fn log_path_for(video_name: &Path) -> PathBuf {
+    let mut name = video_name.as_os_str().to_os_string();
+    name.push(".ffmpeg.log");
+    PathBuf::from(name)
+}
+
+// Keep the seed with the video so that it can be rendered again
+fn metadata_args(config: &RenderConfig) -> [String; 2] {
+    ["-metadata".to_string(), format!("comment=slideshow seed {}", config.seed)]
+}
+
+fn spawn_error(e: std::io::Error) -> anyhow::Error {
+    match e.kind() {
+        ErrorKind::NotFound => anyhow!("ffmpeg not found, make sure it is installed and in your PATH"),
+        _ => anyhow!("Failed to start ffmpeg process: {}", e),
+    }
+}
+
+/// Last lines of a log file, quoted in error messages
+pub fn log_tail(log_path: &Path) -> String {
+    let log = std::fs::read_to_string(log_path).unwrap_or_default();
+    let lines: Vec<&str> = log.lines().collect();
+    lines[lines.len().saturating_sub(LOG_TAIL_LINES)..].join("\n")
+}
+
+fn failure(log_path: &Path, status: ExitStatus) -> anyhow::Error {
+    anyhow!("ffmpeg failed ({}), see {}:\n{}", status, log_path.display(), log_tail(log_path))
+}
+
+/// Joins video segments without re-encoding them (concat demuxer) and adds the music, from
+/// `start_time` in the track: the time of the first frame in the slideshow. The music is cut
+/// to the length of the video, or followed by silence when shorter: the video is always whole.
+pub fn concat(config: &RenderConfig, encoder: &EncoderOptions, music: Option<&Path>, start_time: f32, segments: &[PathBuf], video_name: &Path, log_path: &Path) -> Result<()> {
+    let mut list_path = video_name.as_os_str().to_os_string();
+    list_path.push(".segments.txt");
+    let list_path = PathBuf::from(list_path);
+
+    let mut list = String::new();
+    for segment in segments {
+        let segment = segment.canonicalize()
+            .with_context(|| format!("Missing segment {}", segment.display()))?;
+        list.push_str(&format!("file '{}'\n", segment.to_string_lossy().replace('\'', "'\\''")));
+    }
+    std::fs::write(&list_path, list)
+        .with_context(|| format!("Failed to write {}", list_path.display()))?;
+
+    let log_file = File::create(log_path)
+        .with_context(|| format!("Failed to create ffmpeg log file {}", log_path.display()))?;
+
+    let mut command = Command::new(&encoder.ffmpeg_program);
+    #[cfg(unix)]
+    command.process_group(0); // Ctrl-C is for the slideshow, not for ffmpeg
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::from(log_file))
+        .args(["-loglevel", "verbose"])
+        .arg("-y")
+        .args(["-f", "concat", "-safe", "0"])
+        .arg("-i").arg(&list_path);
+
+    if let Some(music) = music {
+        if start_time > 0.0 {
+            command.args(["-ss", &format!("{}", start_time)]);
+        }
+        command
+            .arg("-i").arg(music)
+            .args(["-map", "0:v", "-map", "1:a"])
+            .args(["-c:a", encoder.audio_codec()])
+            // Silence after the end of a short track, then cut at the end of the video
+            .args(["-af", "apad"])
+            .arg("-shortest");
+    }
+
+    let status = command
+        .args(["-c:v", "copy"])
+        .args(encoder.container_args())
+        .args(metadata_args(config))
+        .arg(video_name)
+        .status()
+        .map_err(spawn_error);
+    let _ = std::fs::remove_file(&list_path);
+
+    let status = status?;
+    if !status.success() {
+        return Err(failure(log_path, status));
+    }
+    Ok(())
 }
 
 impl Ffmpeg {
-    pub fn new(width: i32, height: i32, fps: u32, video_name: &str) -> Ffmpeg {
-        let mut process = Command::new("ffmpeg")
+    /// Video stream only, in a Matroska file, to be joined with the other segments by `concat`
+    pub fn segment(config: &RenderConfig, encoder: &EncoderOptions, video_name: &Path, log_path: &Path) -> Result<Ffmpeg> {
+        let mut command = Self::command(config, encoder, log_path)?;
+        command
+            .args(encoder.codec_args())
+            .args(["-f", "matroska"])
+            .arg(video_name);
+        Self::spawn(command, log_path)
+    }
+
+    /// Encodes frames and throws them away, to measure the rendering speed
+    pub fn null(config: &RenderConfig, encoder: &EncoderOptions, log_path: &Path) -> Result<Ffmpeg> {
+        let mut command = Self::command(config, encoder, log_path)?;
+        command
+            .args(encoder.codec_args())
+            .args(["-f", "null", "-"]);
+        Self::spawn(command, log_path)
+    }
+
+    /// ffmpeg reading raw frames from stdin, logging to `log_path`
+    fn command(config: &RenderConfig, encoder: &EncoderOptions, log_path: &Path) -> Result<Command> {
+        let log_file = File::create(log_path)
+            .with_context(|| format!("Failed to create ffmpeg log file {}", log_path.display()))?;
+
+        let mut command = Command::new(&encoder.ffmpeg_program);
+        // Out of the terminal's process group: Ctrl-C only reaches the slideshow, which then
+        // closes the input so that ffmpeg finalizes the video
+        #[cfg(unix)]
+        command.process_group(0);
+        command
             .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::from(log_file))
             .args(["-loglevel", "verbose"])
             .arg("-y")
             .args(["-f", "rawvideo"])
             .args(["-pixel_format", "rgba"])
-            .args(["-video_size", &format!("{}x{}", width, height)])
-            .args(["-framerate", &format!("{}", fps)])
-            .args(["-i", "-"])
-            .args(["-c:v", "libx264"])
-            // .args(["-preset", "ultrafast"])
-            .args(["-pix_fmt", "yuv420p"])
-            .arg(video_name)
+            .args(["-video_size", &format!("{}x{}", config.width, config.height)])
+            .args(["-framerate", &format!("{}", config.fps)])
+            .args(["-i", "-"]);
+        Ok(command)
+    }
+
+    fn spawn(mut command: Command, log_path: &Path) -> Result<Ffmpeg> {
+        let mut process = command
             .spawn()
-            .expect("Failed to start ffmpeg process");
-        let stdin = process.stdin.take().expect("Failed to open ffmpeg stdin");
-        Ffmpeg { process, stdin: Some(stdin) }
-    }
-
-    pub fn write(&mut self, image: &Image) {
-        unsafe {
-            let image_ptr = image.data() as *const u8;
-            let image_len = (image.width() * image.height() * 4) as usize; // 4 bytes per pixel (RGBA)
-            let image_slice = std::slice::from_raw_parts(image_ptr, image_len);
-
-            // Write the image data flipped vertically to ffmpeg stdin
-            // This is necessary because ffmpeg expects the image data in a specific order
-            // (top to bottom), while raylib provides it in bottom to top order.
-
-            for y in 0..image.height() {
-                let row_start = (image.height() - 1 - y) * image.width() * 4;
-                let row_end = row_start + image.width() * 4;
-                let row_slice = &image_slice[row_start as usize..row_end as usize];
-                self.stdin.as_mut().unwrap().write_all(row_slice).expect("Failed to write to ffmpeg stdin");
+            .map_err(spawn_error)?;
+        let stdin = process.stdin.take().context("Failed to open ffmpeg stdin")?;
+        Ok(Ffmpeg { process, stdin: Some(stdin), log_path: log_path.to_path_buf() })
+    }
+
+    /// Writes one frame, as RGBA rows from top to bottom
+    pub fn write(&mut self, frame: &[u8]) -> Result<()> {
+        // ffmpeg may have given up (bad option, disk full...) while we were rendering
+        if let Some(status) = self.process.try_wait().context("Failed to check ffmpeg process")? {
+            self.stdin = None;
+            if status.success() {
+                bail!("ffmpeg exited before the end of the video");
             }
+            return Err(self.failure(status));
         }
+
+        let stdin = self.stdin.as_mut().context("ffmpeg input is already closed")?;
+        let result = stdin.write_all(frame);
+
+        if let Err(e) = result {
+            // Broken pipe: ffmpeg exited, report its status rather than the pipe error
+            self.stdin = None;
+            let status = self.process.wait().context("Failed to wait for ffmpeg process")?;
+            if status.success() {
+                bail!("ffmpeg exited before the end of the video: {}", e);
+            }
+            return Err(self.failure(status));
+        }
+        Ok(())
+    }
+
+    /// Closes the input and waits for ffmpeg to finalize the video
+    pub fn finish(mut self) -> Result<()> {
+        self.stdin = None; // force drop, ffmpeg sees the end of the stream
+        let status = self.process.wait().context("Failed to wait for ffmpeg process")?;
+        if !status.success() {
+            return Err(self.failure(status));
+        }
+        Ok(())
+    }
+
+    fn failure(&self, status: ExitStatus) -> anyhow::Error {
+        failure(&self.log_path, status)
     }
 }
 
 impl Drop for Ffmpeg {
     fn drop(&mut self) {
-        // Close stdin pipe and wait for ffmpeg to finish
+        // Close stdin pipe and wait for ffmpeg to finish (no-op once finished)
         self.stdin = None; // force drop
-        self.process.wait().expect("Failed to wait for ffmpeg process");
+        let _ = self.process.wait();
+    }
+}
+
+// The fake ffmpeg is a shell script
+#[cfg(all(test, unix))]
+pub(crate) mod tests {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+    use super::*;
+    use crate::timing::Timing;
+
+    /// A fake `ffmpeg` shell script, in a temporary directory removed on drop
+    pub struct StubFfmpeg {
+        pub dir: PathBuf,
+        program: PathBuf,
     }
-}
\ No newline at end of file
+
+    impl StubFfmpeg {
+        /// `script` is the body of the shell script, None leaves the directory without ffmpeg
+        pub fn new(name: &str, script: Option<&str>) -> Self {
+            let dir = std::env::temp_dir().join(format!("slideshow-test-{}-{}", std::process::id(), name));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+
+            let program = dir.join("ffmpeg");
+            if let Some(script) = script {
+                std::fs::write(&program, format!("#!/bin/sh\n{}\n", script)).unwrap();
+                std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+            }
+            Self { dir, program }
+        }
+
+        /// Default encoder options running the stub
+        pub fn encoder(&self) -> EncoderOptions {
+            let mut encoder = EncoderOptions::preset("default").unwrap();
+            encoder.ffmpeg_program = self.program.clone();
+            encoder
+        }
+
+        pub fn log_path(&self) -> PathBuf {
+            self.dir.join("video.ffmpeg.log")
+        }
+    }
+
+    impl Drop for StubFfmpeg {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    pub fn config() -> RenderConfig {
+        RenderConfig::new(2, 2, 30, Timing::default(), 1).unwrap()
+    }
+
+    fn start(stub: &StubFfmpeg) -> Result<Ffmpeg> {
+        Ffmpeg::null(&config(), &stub.encoder(), &stub.log_path())
+    }
+
+    /// Writes frames until ffmpeg fails, which must happen within a few seconds
+    fn write_until_error(ffmpeg: &mut Ffmpeg) -> String {
+        let frame = [0u8; 16];
+        for _ in 0..500 {
+            if let Err(e) = ffmpeg.write(&frame) {
+                return format!("{:#}", e);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("ffmpeg never failed");
+    }
+
+    #[test]
+    fn missing_ffmpeg_is_reported() {
+        let stub = StubFfmpeg::new("missing", None);
+        let error = start(&stub).err().expect("ffmpeg should be missing");
+        assert!(error.to_string().contains("ffmpeg not found"), "{}", error);
+    }
+
+    #[test]
+    fn failure_at_startup_reports_status_and_log() {
+        let stub = StubFfmpeg::new("startup", Some("echo \"Unknown encoder 'libfoo'\" >&2\nexit 1"));
+        let mut ffmpeg = start(&stub).unwrap();
+        let error = write_until_error(&mut ffmpeg);
+        assert!(error.contains("exit status: 1"), "{}", error);
+        assert!(error.contains("Unknown encoder 'libfoo'"), "{}", error);
+    }
+
+    #[test]
+    fn failure_mid_render_reports_status_and_log() {
+        let stub = StubFfmpeg::new("mid-render", Some("head -c 64 > /dev/null\necho 'No space left on device' >&2\nexit 1"));
+        let mut ffmpeg = start(&stub).unwrap();
+        let error = write_until_error(&mut ffmpeg);
+        assert!(error.contains("exit status: 1"), "{}", error);
+        assert!(error.contains("No space left on device"), "{}", error);
+    }
+
+    #[test]
+    fn closed_input_is_reported_as_an_early_exit() {
+        let stub = StubFfmpeg::new("broken-pipe", Some("exec 0<&-\nsleep 0.2\nexit 0"));
+        let mut ffmpeg = start(&stub).unwrap();
+        let error = write_until_error(&mut ffmpeg);
+        assert!(error.starts_with("ffmpeg exited before the end of the video"), "{}", error);
+    }
+
+    #[test]
+    fn early_exit_with_success_status_is_not_reported_as_a_failure() {
+        let stub = StubFfmpeg::new("early-exit", Some("exit 0"));
+        let mut ffmpeg = start(&stub).unwrap();
+        let error = write_until_error(&mut ffmpeg);
+        assert!(error.starts_with("ffmpeg exited before the end of the video"), "{}", error);
+        assert!(!error.contains("exit status"), "{}", error);
+    }
+
+    #[test]
+    fn finish_succeeds_when_ffmpeg_does() {
+        let stub = StubFfmpeg::new("finish-ok", Some("cat > /dev/null"));
+        let mut ffmpeg = start(&stub).unwrap();
+        ffmpeg.write(&[0u8; 16]).unwrap();
+        ffmpeg.finish().unwrap();
+    }
+
+    #[test]
+    fn finish_reports_the_exit_status() {
+        let stub = StubFfmpeg::new("finish-failed", Some("cat > /dev/null\necho 'Conversion failed!' >&2\nexit 1"));
+        let mut ffmpeg = start(&stub).unwrap();
+        ffmpeg.write(&[0u8; 16]).unwrap();
+        let error = format!("{:#}", ffmpeg.finish().expect_err("ffmpeg should fail"));
+        assert!(error.contains("Conversion failed!"), "{}", error);
+    }
+
+    /// Script line writing `<pid> <process group id>` of the stub to `pgid` in its directory
+    #[cfg(target_os = "linux")]
+    const RECORD_PROCESS_GROUP: &str = "set -- $(cat /proc/$$/stat)\necho \"$$ $5\" > \"$(dirname \"$0\")/pgid\"";
+
+    /// Checks that the stub led its own process group, away from the test's
+    #[cfg(target_os = "linux")]
+    fn assert_own_process_group(stub: &StubFfmpeg) {
+        let recorded = std::fs::read_to_string(stub.dir.join("pgid")).unwrap();
+        let (pid, pgid) = recorded.trim().split_once(' ').unwrap();
+        assert_eq!(pid, pgid, "ffmpeg should lead its own process group");
+
+        let stat = std::fs::read_to_string("/proc/self/stat").unwrap();
+        let own_pgid = stat.rsplit(')').next().unwrap().split_whitespace().nth(2).unwrap();
+        assert_ne!(pgid, own_pgid, "ffmpeg should not be in the process group receiving Ctrl-C");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn encoding_ffmpeg_is_out_of_the_terminal_process_group() {
+        let stub = StubFfmpeg::new("process-group", Some(&format!("{}\ncat > /dev/null", RECORD_PROCESS_GROUP)));
+        let mut ffmpeg = start(&stub).unwrap();
+        ffmpeg.write(&[0u8; 16]).unwrap();
+        ffmpeg.finish().unwrap();
+        assert_own_process_group(&stub);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn concat_ffmpeg_is_out_of_the_terminal_process_group() {
+        let stub = StubFfmpeg::new("concat-process-group", Some(RECORD_PROCESS_GROUP));
+        let segment = stub.dir.join("show.segment-1.mkv");
+        std::fs::write(&segment, b"").unwrap();
+        concat(&config(), &stub.encoder(), None, 0.0, &[segment], &stub.dir.join("show.mp4"), &stub.log_path()).unwrap();
+        assert_own_process_group(&stub);
+    }
+
+    #[test]
+    fn music_is_padded_and_cut_to_the_video() {
+        let stub = StubFfmpeg::new("concat-music", Some("echo \"$@\" > \"$(dirname \"$0\")/args\""));
+        let segment = stub.dir.join("show.segment-1.mkv");
+        let music = stub.dir.join("music.mp3");
+        std::fs::write(&segment, b"").unwrap();
+        std::fs::write(&music, b"").unwrap();
+        concat(&config(), &stub.encoder(), Some(&music), 12.5, &[segment], &stub.dir.join("show.mp4"), &stub.log_path()).unwrap();
+
+        let args = std::fs::read_to_string(stub.dir.join("args")).unwrap();
+        assert!(args.contains(&format!("-ss 12.5 -i {}", music.display())), "{}", args);
+        assert!(args.contains("-af apad") && args.contains("-shortest"), "{}", args);
+    }
+}
@@ -0,0 +1,125 @@
+/// Container of an image file, recognized from its first bytes rather than its extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    WebP,
+    Heif,
+    Tiff,
+    Bmp,
+    Gif,
+}
+
+/// Major brands of the ISO base media files holding HEIF images
+const HEIF_BRANDS: &[&[u8]] = &[b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis", b"mif1", b"msf1"];
+
+impl ImageFormat {
+    /// Recognizes the container from the first bytes of a file
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Self::Jpeg)
+        } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(&b"WEBP"[..]) {
+            Some(Self::WebP)
+        } else if bytes.get(4..8) == Some(&b"ftyp"[..]) && bytes.get(8..12).is_some_and(|brand| HEIF_BRANDS.contains(&brand)) {
+            Some(Self::Heif)
+        } else if bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*") {
+            Some(Self::Tiff)
+        } else if bytes.starts_with(b"BM") {
+            Some(Self::Bmp)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "JPEG",
+            Self::Png => "PNG",
+            Self::WebP => "WebP",
+            Self::Heif => "HEIF",
+            Self::Tiff => "TIFF",
+            Self::Bmp => "BMP",
+            Self::Gif => "GIF",
+        }
+    }
+
+    /// File type hint given to raylib when loading from memory
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Jpeg => ".jpg",
+            Self::Png => ".png",
+            Self::WebP => ".webp",
+            Self::Heif => ".heic",
+            Self::Tiff => ".tif",
+            Self::Bmp => ".bmp",
+            Self::Gif => ".gif",
+        }
+    }
+
+    /// Whether kamadak-exif can read EXIF data from this container. Only the ones raylib can
+    /// decode are read, the others are skipped before.
+    pub fn has_exif(&self) -> bool {
+        !matches!(self, Self::Bmp | Self::Gif)
+    }
+
+    /// Whether raylib can decode the pixels of this container
+    pub fn is_decodable(&self) -> bool {
+        matches!(self, Self::Jpeg | Self::Png | Self::Bmp | Self::Gif)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn containers_are_recognized_from_their_first_bytes() {
+        let files: &[(&[u8], ImageFormat)] = &[
+            (b"\xFF\xD8\xFF\xE1\0\x22Exif\0\0", ImageFormat::Jpeg),
+            (b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", ImageFormat::Png),
+            (b"RIFF\x24\0\0\0WEBPVP8 ", ImageFormat::WebP),
+            (b"\0\0\0\x18ftypheic\0\0\0\0", ImageFormat::Heif),
+            (b"\0\0\0\x1cftypmif1\0\0\0\0", ImageFormat::Heif),
+            (b"II*\0\x08\0\0\0", ImageFormat::Tiff),
+            (b"MM\0*\0\0\0\x08", ImageFormat::Tiff),
+            (b"BM\x36\0\x0c\0", ImageFormat::Bmp),
+            (b"GIF89a\x01\0\x01\0", ImageFormat::Gif),
+        ];
+        for (bytes, format) in files {
+            assert_eq!(ImageFormat::detect(bytes), Some(*format), "{}", format.name());
+        }
+    }
+
+    #[test]
+    fn a_png_saved_as_jpg_is_a_png() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/png-saved-as.jpg");
+        let format = ImageFormat::detect(&std::fs::read(path).unwrap());
+        assert_eq!(format, Some(ImageFormat::Png));
+        assert!(format.unwrap().is_decodable());
+    }
+
+    #[test]
+    fn other_files_are_not_recognized() {
+        for bytes in [&b""[..], b"\xFF\xD8", b"hero = true\n", b"RIFF\x24\0\0\0WAVEfmt ", b"\0\0\0\x18ftypisom\0\0\0\0", b"GIF90a"] {
+            assert_eq!(ImageFormat::detect(bytes), None, "{:?}", bytes);
+        }
+    }
+
+    #[test]
+    fn only_jpeg_png_bmp_and_gif_can_be_displayed() {
+        let displayable: Vec<&str> = [
+            ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::WebP, ImageFormat::Heif,
+            ImageFormat::Tiff, ImageFormat::Bmp, ImageFormat::Gif,
+        ]
+            .into_iter()
+            .filter(ImageFormat::is_decodable)
+            .map(|format| format.name())
+            .collect();
+        assert_eq!(displayable, vec!["JPEG", "PNG", "BMP", "GIF"]);
+    }
+}
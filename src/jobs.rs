@@ -0,0 +1,317 @@
+use std::io::{BufRead, BufReader, Write};
+use std::fs::File;
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::json;
+use crate::checkpoint::{checkpoint_path, Checkpoint};
+use crate::cli::Settings;
+use crate::ffmpeg::log_tail;
+use crate::progress::Progress;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Part `index` (from 1) of `count` of the segments of the video, rendered by a worker process:
+/// `--chunk 2/8`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chunk {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Chunk {
+    /// Share of this chunk of `items`, shared evenly and in order between chunks
+    pub fn share<'a, T>(&self, items: &'a [T]) -> &'a [T] {
+        let start = items.len() * (self.index - 1) / self.count;
+        let end = items.len() * self.index / self.count;
+        &items[start..end]
+    }
+}
+
+pub fn parse_chunk(text: &str) -> Result<Chunk, String> {
+    let invalid = || format!("Invalid chunk '{}' (expected INDEX/COUNT, e.g. 2/8)", text);
+    let (index, count) = text.split_once('/').ok_or_else(invalid)?;
+    let index: usize = index.parse().map_err(|_| invalid())?;
+    let count: usize = count.parse().map_err(|_| invalid())?;
+    if index == 0 || index > count {
+        return Err(invalid());
+    }
+    Ok(Chunk { index, count })
+}
+
+/// Reports the frames of the render and the length of its segments on stdout, like the
+/// progress, before rendering: the parent process learns which segments to join
+pub fn report_segments(checkpoint: &Checkpoint) {
+    println!("{}", json!({
+        "event": "segments",
+        "frames": checkpoint.frames,
+        "segment_frames": checkpoint.segment_frames,
+    }));
+    let _ = std::io::stdout().flush();
+}
+
+/// Reports on stdout that segment `index` is complete, for the parent process to record it in
+/// the checkpoint
+pub fn report_segment(index: usize) {
+    println!("{}", json!({ "event": "segment", "index": index }));
+    let _ = std::io::stdout().flush();
+}
+
+/// Messages of worker `index`: `<name>.worker-<index>.log` next to the final video
+fn worker_log_path(output: &Path, index: usize) -> PathBuf {
+    let stem = output.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    output.with_file_name(format!("{}.worker-{}.log", stem, index))
+}
+
+struct Worker {
+    process: Child,
+    reader: JoinHandle<Result<()>>,
+}
+
+/// What the workers reported on stdout
+#[derive(Default)]
+struct Reports {
+    progress: Vec<(u64, u64)>,         // frames written and to write by each worker
+    segments: Option<([u64; 2], u64)>, // frames of the render and length of its segments
+    started: usize,                    // workers which reported the segments
+    completed: Vec<usize>,             // segments completed since the last check
+}
+
+impl Reports {
+    /// Records the segments completed since the last call in `checkpoint`, created from the
+    /// segments reported by the workers unless the render is resumed. Returns true if it changed.
+    fn record(&mut self, settings: &Settings, checkpoint: &mut Option<Checkpoint>) -> bool {
+        if let Some(([start, end], segment_frames)) = self.segments.filter(|_| checkpoint.is_none()) {
+            *checkpoint = Some(Checkpoint::new(settings, start..end, segment_frames));
+        }
+        let Some(checkpoint) = checkpoint else {
+            return false;
+        };
+        let completed = !self.completed.is_empty();
+        checkpoint.completed.extend(self.completed.drain(..));
+        completed
+    }
+}
+
+/// Renders the slideshow with `settings.jobs` worker processes, each one encoding its share of
+/// the segments left to render by a single process render. Returns the checkpoint of the render,
+/// None if it was interrupted before the workers started rendering: join_segments() makes the
+/// output video from it.
+///
+/// Workers run this same executable with the same arguments, plus their chunk. They encode the
+/// same frames to the same segments as a single process would: the video is the same. They report
+/// each complete segment, recorded here in the checkpoint so that --resume works as without --jobs.
+///
+/// Workers run in their own process groups, out of reach of Ctrl-C: once `interrupted` is set
+/// they are interrupted here, and finalize the segment they were writing.
+pub fn render_parallel(settings: &Settings, interrupted: &AtomicBool) -> Result<Option<Checkpoint>> {
+    let jobs = settings.jobs;
+    let executable = std::env::current_exe().context("Cannot find the slideshow executable")?;
+    let args: Vec<_> = std::env::args_os().skip(1).collect();
+
+    let reports = Arc::new(Mutex::new(Reports { progress: vec![(0, 0); jobs], ..Default::default() }));
+
+    let mut workers: Vec<Worker> = Vec::new();
+    for index in 1..=jobs {
+        let log_path = worker_log_path(&settings.output, index);
+        // Workers report their progress on stdout, their messages would flood the terminal
+        let log = match File::create(&log_path) {
+            Ok(log) => log,
+            Err(e) => {
+                stop(workers);
+                bail!("Failed to create {}: {}", log_path.display(), e);
+            }
+        };
+        let mut command = Command::new(&executable);
+        #[cfg(unix)]
+        command.process_group(0);
+        let spawned = command
+            .args(&args)
+            // Workers must draw the same random layout
+            .args(["--seed", &settings.config.seed.to_string()])
+            .args(["--chunk", &format!("{}/{}", index, jobs)])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::from(log))
+            .spawn();
+        let mut process = match spawned {
+            Ok(process) => process,
+            Err(e) => {
+                stop(workers);
+                bail!("Failed to start worker {}/{}: {}", index, jobs, e);
+            }
+        };
+
+        let stdout = process.stdout.take().context("Failed to read worker output")?;
+        let reports = Arc::clone(&reports);
+        let reader = std::thread::spawn(move || -> Result<()> {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                let Ok(event) = serde_json::from_str::<serde_json::Value>(&line) else {
+                    continue;
+                };
+                let mut reports = reports.lock().map_err(|_| anyhow!("Worker progress is unavailable"))?;
+                match event["event"].as_str() {
+                    Some("segments") => {
+                        let frames = serde_json::from_value(event["frames"].clone())?;
+                        let segment_frames = event["segment_frames"].as_u64().unwrap_or(1);
+                        reports.segments = Some((frames, segment_frames));
+                        reports.started += 1;
+                    }
+                    Some("segment") => {
+                        if let Some(index) = event["index"].as_u64() {
+                            reports.completed.push(index as usize);
+                        }
+                    }
+                    _ => {
+                        let frame = event["frame"].as_u64().unwrap_or(0);
+                        let total_frames = event["total_frames"].as_u64().unwrap_or(0);
+                        reports.progress[index - 1] = (frame, total_frames);
+                    }
+                }
+            }
+            Ok(())
+        });
+        workers.push(Worker { process, reader });
+    }
+    eprintln!("Rendering with {} worker processes", jobs);
+
+    let checkpoint_path = checkpoint_path(&settings.output);
+    let mut checkpoint = settings.resume.clone();
+    let mut progress = Progress::new(settings.progress, 0);
+    let mut failed = None;
+    let frame = loop {
+        let mut running = false;
+        for (i, worker) in workers.iter_mut().enumerate() {
+            match worker.process.try_wait().context("Failed to check worker process")? {
+                None => running = true,
+                Some(status) if !status.success() => failed = failed.or(Some((i, status))),
+                Some(_) => {}
+            }
+        }
+
+        // Poisoned if a progress reader panicked
+        let Ok(mut reports) = reports.lock() else {
+            stop(workers);
+            bail!("Worker progress is unavailable");
+        };
+        // Workers read the checkpoint of a resumed render when they start, it is only written
+        // once they all did
+        let save = reports.record(settings, &mut checkpoint) && reports.started == jobs;
+        if let Some(checkpoint) = checkpoint.as_ref().filter(|_| save) {
+            checkpoint.save(&checkpoint_path)?;
+        }
+        let (written, total_frames) = reports.progress.iter()
+            .fold((0, 0), |(written, total), (frame, frames)| (written + frame, total + frames));
+        drop(reports);
+        progress.set_total_frames(total_frames);
+        progress.update(written);
+
+        if !running || failed.is_some() || interrupted.load(Ordering::Relaxed) {
+            break written;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+
+    // Workers stop after their segment when they are interrupted, which may complete it
+    stop(workers);
+    progress.finish(frame);
+    let mut reports = reports.lock().map_err(|_| anyhow!("Worker progress is unavailable"))?;
+    reports.record(settings, &mut checkpoint);
+    if let Some(checkpoint) = &checkpoint {
+        checkpoint.save(&checkpoint_path)?;
+    }
+
+    if let Some((i, status)) = failed {
+        let log_path = worker_log_path(&settings.output, i + 1);
+        bail!("Worker {}/{} failed ({}), see {}:\n{}", i + 1, jobs, status, log_path.display(), log_tail(&log_path));
+    }
+    if !interrupted.load(Ordering::Relaxed) {
+        if !checkpoint.as_ref().is_some_and(Checkpoint::is_complete) {
+            bail!("The workers did not render every segment");
+        }
+        for index in 1..=jobs {
+            let _ = std::fs::remove_file(worker_log_path(&settings.output, index));
+        }
+    }
+    Ok(checkpoint)
+}
+
+/// Interrupts the workers still running and waits for them to exit
+fn stop(mut workers: Vec<Worker>) {
+    for worker in &mut workers {
+        if let Ok(None) = worker.process.try_wait() {
+            interrupt(&mut worker.process);
+        }
+    }
+    for mut worker in workers {
+        let _ = worker.process.wait();
+        let _ = worker.reader.join();
+    }
+}
+
+/// Sends SIGINT, as Ctrl-C would: the worker stops rendering and lets its ffmpeg finalize the
+/// segment it was writing
+#[cfg(unix)]
+fn interrupt(process: &mut Child) {
+    // SAFETY: kill() only sends a signal, to a child process not waited for yet
+    unsafe { libc::kill(process.id() as libc::pid_t, libc::SIGINT) };
+}
+
+/// Without signals the worker is killed, along with the segment it was writing
+#[cfg(not(unix))]
+fn interrupt(process: &mut Child) {
+    let _ = process.kill();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_share_all_the_segments() {
+        for segment_count in [0, 1, 7, 20, 21] {
+            let segments: Vec<usize> = (1..=segment_count).collect();
+            for count in 1..=8 {
+                let shares: Vec<&[usize]> = (1..=count).map(|index| Chunk { index, count }.share(&segments)).collect();
+                assert_eq!(shares.concat(), segments, "{} segments in {} chunks", segment_count, count);
+                let sizes: Vec<usize> = shares.iter().map(|share| share.len()).collect();
+                assert!(sizes.iter().max().unwrap() - sizes.iter().min().unwrap() <= 1, "{:?}", sizes);
+            }
+        }
+    }
+
+    #[test]
+    fn worker_logs_are_named_after_the_video() {
+        assert_eq!(worker_log_path(Path::new("out/show.mp4"), 2), PathBuf::from("out/show.worker-2.log"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn workers_are_interrupted_like_with_ctrl_c() {
+        let mut process = Command::new("sh")
+            .args(["-c", "trap 'exit 3' INT\necho ready\nwhile true; do sleep 0.05; done"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // The trap is set once the script writes
+        let mut ready = String::new();
+        BufReader::new(process.stdout.take().unwrap()).read_line(&mut ready).unwrap();
+        interrupt(&mut process);
+        assert_eq!(process.wait().unwrap().code(), Some(3));
+    }
+
+    #[test]
+    fn chunks_are_parsed() {
+        assert_eq!(parse_chunk("2/8"), Ok(Chunk { index: 2, count: 8 }));
+        assert_eq!(parse_chunk("1/1"), Ok(Chunk { index: 1, count: 1 }));
+        for text in ["0/8", "9/8", "2", "2/", "/8", "a/8"] {
+            assert!(parse_chunk(text).is_err(), "{}", text);
+        }
+    }
+}
@@ -1,27 +1,66 @@
-use std::path::Path;
-use std::time::Duration;
+use std::process::ExitCode;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use anyhow::anyhow;
 use raylib::prelude::*;
-use clap::Parser;
 
 mod constants;
+mod config;
+mod timing;
+mod project;
+mod cli;
+mod slide_settings;
+mod caption;
+mod clock;
 mod texture_loader;
+mod image_format;
+mod scan;
+mod sort;
 mod subject_detection;
+mod encoder;
 mod ffmpeg;
+mod pipeline;
+mod readback;
+mod output;
+mod progress;
+mod timecode;
+mod timeline;
+mod date;
 mod engine;
+mod preview;
+mod jobs;
+mod checkpoint;
 
 mod spiral;
 mod push_box;
+mod sequence;
 
-use crate::constants::*;
+use crate::constants::{BENCHMARK_DURATION, PREVIEW_FPS, READBACK_BUFFERS};
+use crate::cli::{App, Settings};
 use crate::texture_loader::*;
+use crate::slide_settings::load_slide_sources;
+use crate::scan::scan_images;
+use crate::sort::sort_images;
 use crate::ffmpeg::*;
-use crate::engine::Engine;
+use crate::output::{finalize_output, partial_path, segment_path};
+use crate::checkpoint::{checkpoint_path, Checkpoint};
+use crate::jobs::Chunk;
+use crate::pipeline::FrameWriter;
+use crate::readback::Readback;
+use crate::progress::Progress;
+use crate::engine::{find_engine, print_engines, Engine};
+use crate::preview::run_preview;
 
-use crate::spiral::engine::SpiralEngine;
-use crate::push_box::engine::PushBoxEngine;
+/// Set by Ctrl-C, the render stops after the current frame as when the window is closed
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
-fn display_error(rl: &mut RaylibHandle, thread: &RaylibThread, error: &str) {
+fn display_error(rl: &mut RaylibHandle, thread: &RaylibThread, headless: bool, error: &str) {
     eprintln!("{}", error);
+    if headless {
+        return;
+    }
     let mut d = rl.begin_drawing(&thread);
     d.clear_background(Color::BLACK);
     d.draw_text(error, 20, 20, 20, Color::RED);
@@ -29,95 +68,453 @@ fn display_error(rl: &mut RaylibHandle, thread: &RaylibThread, error: &str) {
     std::thread::sleep(Duration::from_secs(5));
 }
 
-#[derive(Debug, Parser)]
-#[command(version, about, long_about = None)]
-pub struct App {
-    #[arg(short, long, help = "Engine to use (spiral or push-box)")]
-    engine: String,
-    
-    #[arg(short, long, help = "Directory containing images")]
-    directory: String,
+/// Renders the frames in `frames`, reads them back with `readback` and sends them to `writer`.
+/// Returns the number of frames written and false if the window was closed first.
+#[allow(clippy::too_many_arguments)]
+fn render(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    settings: &Settings,
+    engine: &dyn Engine,
+    framebuffer: &mut RenderTexture2D,
+    readback: &mut Readback,
+    writer: &mut FrameWriter,
+    progress: &mut Progress,
+    frames: Range<u64>,
+) -> anyhow::Result<(u64, bool)> {
+    let config = &settings.config;
+    let mut written: u64 = 0;
+    let done = progress.frame(); // frames of the previous segments
+
+    // The preview is refreshed at a fixed wall-clock rate, whatever the rendering speed
+    let preview_interval = Duration::from_secs_f32(1.0 / PREVIEW_FPS as f32);
+    let mut last_preview: Option<Instant> = None;
+
+    for frame in frames {
+        if rl.window_should_close() || INTERRUPTED.load(Ordering::Relaxed) {
+            readback.flush(writer)?;
+            return Ok((written, false));
+        }
+
+        engine.render_at(config.frame_timestamp(frame), rl, thread, framebuffer);
+
+        // Draw inverted copy of framebuffer to the screen for feedback
+        if !settings.headless && last_preview.is_none_or(|t| t.elapsed() >= preview_interval) {
+            last_preview = Some(Instant::now());
+            let mut d = rl.begin_drawing(thread);
+            
+            let sw = d.get_screen_width() as f32;
+            let sh = d.get_screen_height() as f32;
+
+            d.draw_texture_pro(
+                &*framebuffer,
+                Rectangle::new(0.0, 0.0, framebuffer.width() as f32, -(framebuffer.height() as f32)),
+                Rectangle::new(0.0, 0.0, sw, sh),
+                Vector2::new(0.0, 0.0),
+                0.0,
+                Color::WHITE
+            );
+        }
+
+        // Grab rendered texture pixels (GPU readback, must happen on this thread) for the ffmpeg pipe
+        readback.read(framebuffer, writer)?;
+
+        written += 1;
+        progress.update(done + written);
+    }
+    readback.flush(writer)?;
+    Ok((written, true))
+}
+
+/// Frames are read back asynchronously when they are encoded on a writer thread: both let the
+/// render thread go on with the next frames
+fn readback_for(settings: &Settings) -> Readback {
+    if settings.pipeline_depth == 0 {
+        return Readback::Sync;
+    }
+    Readback::new(settings.config.width, settings.config.height, READBACK_BUFFERS)
+}
+
+/// How rendering segments ended
+enum Segments {
+    Complete,
+    Interrupted(Option<usize>), // the segment cut short, finalized by ffmpeg so it is playable too
+}
+
+/// Renders every segment of `segments` (index and frames) to its own file, calling `completed`
+/// after each complete one
+#[allow(clippy::too_many_arguments)]
+fn render_segments(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    settings: &Settings,
+    engine: &dyn Engine,
+    framebuffer: &mut RenderTexture2D,
+    segments: &[(usize, Range<u64>)],
+    progress: &mut Progress,
+    mut completed: impl FnMut(usize) -> anyhow::Result<()>,
+) -> anyhow::Result<Segments> {
+    let config = &settings.config;
+    let mut readback = readback_for(settings);
+
+    for (index, frames) in segments {
+        let segment = segment_path(&settings.output, *index);
+        let ffmpeg = Ffmpeg::segment(config, &settings.encoder, &segment, &log_path_for(&segment))?;
+        let mut writer = FrameWriter::new(ffmpeg, settings.pipeline_depth);
+
+        let (written, complete) = render(rl, thread, settings, engine, framebuffer, &mut readback, &mut writer, progress, frames.clone())?;
+        writer.finish()?;
+        if !complete {
+            if written > 0 {
+                return Ok(Segments::Interrupted(Some(*index)));
+            }
+            let _ = std::fs::remove_file(&segment);
+            return Ok(Segments::Interrupted(None));
+        }
+        completed(*index)?;
+    }
+    Ok(Segments::Complete)
+}
+
+/// Joins the segments of `checkpoint` into the output video, then deletes them with the
+/// checkpoint. Returns false if some are missing: the complete segments from the start of the
+/// video, followed by `cut_short` if it comes next, are joined into a playable partial video and
+/// kept with the checkpoint, for --resume.
+fn join_segments(settings: &Settings, checkpoint: &Checkpoint, cut_short: Option<usize>) -> anyhow::Result<bool> {
+    let config = &settings.config;
+    let complete_prefix = checkpoint.complete_prefix();
+    let mut segments: Vec<PathBuf> = (1..=complete_prefix)
+        .map(|index| segment_path(&settings.output, index))
+        .collect();
+    if cut_short == Some(complete_prefix + 1) {
+        segments.push(segment_path(&settings.output, complete_prefix + 1));
+    }
+    if segments.is_empty() {
+        return Ok(false);
+    }
+
+    // Encode to a temporary file, renamed once the video is complete
+    let partial_output = partial_path(&settings.output);
+    let start_time = config.frame_timestamp(checkpoint.frames[0]);
+    eprintln!("Joining {} segment(s)", segments.len());
+    concat(config, &settings.encoder, settings.music.as_deref(), start_time, &segments, &partial_output, &log_path_for(&settings.output))?;
+    if !checkpoint.is_complete() {
+        return Ok(false);
+    }
+
+    finalize_output(&partial_output, &settings.output, settings.force).map_err(|e| anyhow!(e))?;
+    for segment in segments {
+        let _ = std::fs::remove_file(log_path_for(&segment));
+        let _ = std::fs::remove_file(segment);
+    }
+    let _ = std::fs::remove_file(checkpoint_path(&settings.output));
+    Ok(true)
+}
+
+/// Renders `frames` to the output video in segments of CHECKPOINT_INTERVAL seconds, recording
+/// each complete segment in a checkpoint, then joins the segments. Returns false if the render
+/// was interrupted, see join_segments().
+fn render_video(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    settings: &Settings,
+    engine: &dyn Engine,
+    framebuffer: &mut RenderTexture2D,
+    frames: Range<u64>,
+) -> anyhow::Result<bool> {
+    let config = &settings.config;
+    let checkpoint_path = checkpoint_path(&settings.output);
+    let mut checkpoint = Checkpoint::resume_or_new(settings, &frames).map_err(|e| anyhow!(e))?;
+    checkpoint.save(&checkpoint_path)?;
+
+    if !checkpoint.completed.is_empty() {
+        eprintln!("Resuming with {}/{} segments complete", checkpoint.completed.len(), checkpoint.segment_count());
+    }
+    let pending = checkpoint.segments(checkpoint.pending());
+    let frames_to_render = pending.iter().map(|(_, frames)| frames.end - frames.start).sum();
+    eprintln!("Rendering {} frames ({:.1}s)", frames_to_render, frames_to_render as f32 * config.frame_time());
+    let mut progress = Progress::new(settings.progress, frames_to_render);
+
+    let rendered = render_segments(rl, thread, settings, engine, framebuffer, &pending, &mut progress, |index| {
+        checkpoint.completed.insert(index);
+        checkpoint.save(&checkpoint_path)
+    })?;
+    progress.finish(progress.frame());
+
+    let cut_short = match rendered {
+        Segments::Complete => None,
+        Segments::Interrupted(segment) => segment,
+    };
+    join_segments(settings, &checkpoint, cut_short)
+}
+
+/// Renders the share of `chunk` of the segments left to render in a single process render, for
+/// the parent process of --jobs: joined, the segments make the same video. Each complete segment
+/// is reported to the parent, which keeps the checkpoint. Returns false if the render was
+/// interrupted.
+#[allow(clippy::too_many_arguments)]
+fn render_chunk(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    settings: &Settings,
+    engine: &dyn Engine,
+    framebuffer: &mut RenderTexture2D,
+    chunk: Chunk,
+    frames: Range<u64>,
+) -> anyhow::Result<bool> {
+    let config = &settings.config;
+    let checkpoint = Checkpoint::resume_or_new(settings, &frames).map_err(|e| anyhow!(e))?;
+    jobs::report_segments(&checkpoint);
+
+    let pending = checkpoint.pending();
+    let segments = checkpoint.segments(chunk.share(&pending).iter().copied());
+    let frames_to_render = segments.iter().map(|(_, frames)| frames.end - frames.start).sum();
+    eprintln!("Rendering {} frames ({:.1}s)", frames_to_render, frames_to_render as f32 * config.frame_time());
+    let mut progress = Progress::new(settings.progress, frames_to_render);
+
+    let rendered = render_segments(rl, thread, settings, engine, framebuffer, &segments, &mut progress, |index| {
+        jobs::report_segment(index);
+        Ok(())
+    })?;
+    progress.finish(progress.frame());
+    Ok(matches!(rendered, Segments::Complete))
+}
+
+/// Reports how writing the video ended, see join_segments()
+fn report_video(settings: &Settings, result: anyhow::Result<bool>) -> ExitCode {
+    match result {
+        Ok(true) => {
+            eprintln!("Video written to {}", settings.output.display());
+            ExitCode::SUCCESS
+        }
+        Ok(false) => {
+            let partial_output = partial_path(&settings.output);
+            if partial_output.exists() {
+                eprintln!("Render interrupted, the partial video was written to {}", partial_output.display());
+            } else {
+                eprintln!("Render interrupted");
+            }
+            eprintln!("Run the same command with --resume to continue it");
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            eprintln!("Complete segments were kept, run the same command with --resume to continue");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Renders the beginning of the slideshow three times, with ffmpeg encoding to nowhere: reading
+/// back and writing frames synchronously, writing them through the pipeline, then also reading
+/// them back asynchronously. Reports the speed of each pass.
+fn benchmark(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    settings: &Settings,
+    engine: &dyn Engine,
+    framebuffer: &mut RenderTexture2D,
+) -> ExitCode {
+    let config = &settings.config;
+    let log_path = log_path_for(Path::new("benchmark"));
+    let depth = settings.pipeline_depth.max(1);
+    let passes = [(0, false), (depth, false), (depth, true)];
+    let mut speeds = Vec::new();
+
+    for (depth, asynchronous) in passes {
+        let ffmpeg = match Ffmpeg::null(config, &settings.encoder, &log_path) {
+            Ok(ffmpeg) => ffmpeg,
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let mut writer = FrameWriter::new(ffmpeg, depth);
+        let mut readback = if asynchronous {
+            Readback::new(config.width, config.height, READBACK_BUFFERS)
+        } else {
+            Readback::Sync
+        };
+
+        let frames = config.frame_count(engine.total_duration().min(BENCHMARK_DURATION));
+        eprintln!("{}: rendering {} frames", pass_name(depth, asynchronous), frames);
+        let mut progress = Progress::new(settings.progress, frames);
+
+        let start = Instant::now();
+        let result = render(rl, thread, settings, engine, framebuffer, &mut readback, &mut writer, &mut progress, 0..frames)
+            .and_then(|(frame, _)| writer.finish().map(|_| frame));
+        let frame = match result {
+            Ok(frame) => frame,
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        progress.finish(frame);
+        speeds.push(frame as f64 / start.elapsed().as_secs_f64());
+    }
+
+    for (&(depth, asynchronous), fps) in passes.iter().zip(speeds.iter()) {
+        eprintln!("{}: {:.1} fps (x{:.2})", pass_name(depth, asynchronous), fps, fps / speeds[0]);
+    }
+    ExitCode::SUCCESS
 }
 
-fn main() {
-    // --- Get Directory from Command Line ---
-    let args = App::parse();
-
-    // The directory path is the second argument (index 1)
-    let image_directory_path = Path::new(&args.directory); // Borrow the string from the vector
-    let video_name = image_directory_path.file_name().unwrap().to_str().unwrap().to_string() + ".mp4";
-    println!("Input path: {}\nOutput video name: {}", image_directory_path.to_str().unwrap(), video_name);
-
-    let (mut rl, thread) = raylib::init()
-        .size(RENDER_WIDTH / 2, RENDER_HEIGHT / 2)
-        .title("Photo Wall Slideshow")
-        .vsync()
-        .resizable()
-        .build();
-    rl.set_target_fps(FPS);
+fn pass_name(depth: usize, asynchronous: bool) -> String {
+    let readback = if asynchronous { "asynchronous" } else { "synchronous" };
+    format!("Pipeline depth {}, {} readback", depth, readback)
+}
+
+fn main() -> ExitCode {
+    // --- Get Settings from Command Line and Project File ---
+    let app = App::parse_with_engines();
+    if app.list_engines {
+        print_engines();
+        return ExitCode::SUCCESS;
+    }
+    let settings = match app.resolve() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let config = &settings.config;
+
+    for source in settings.sources.iter() {
+        eprintln!("Input path: {}", source.display());
+    }
+    match &settings.png {
+        Some(pattern) => eprintln!("Output PNG files: {}", pattern),
+        None => eprintln!("Output video name: {}", settings.output.display()),
+    }
+    eprintln!("Output format: {}x{} @{}fps", config.width, config.height, config.fps);
+    eprintln!("Seed: {} (use --seed {} to render this video again)", config.seed, config.seed);
+    eprintln!("Encoder: {} {} in {}", settings.encoder.codec, settings.encoder.pix_fmt, settings.encoder.container.extension());
+
+    if settings.jobs > 1 {
+        if let Err(e) = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::Relaxed)) {
+            eprintln!("Warning: Ctrl-C will leave the worker processes running: {}", e);
+        }
+        let result = jobs::render_parallel(&settings, &INTERRUPTED).and_then(|checkpoint| match checkpoint {
+            Some(checkpoint) => join_segments(&settings, &checkpoint, None),
+            None => Ok(false), // interrupted before the workers started rendering
+        });
+        return report_video(&settings, result);
+    }
+
+    let mut builder = raylib::init();
+    builder
+        .size(config.width / 2, config.height / 2)
+        .title("Photo Wall Slideshow");
+    if settings.headless {
+        // Rendering only happens in the offscreen framebuffer, the window is never shown.
+        // SetConfigFlags() adds to the flags set by the builder.
+        unsafe { raylib::ffi::SetConfigFlags(ConfigFlags::FLAG_WINDOW_HIDDEN as u32) };
+    } else if settings.preview {
+        builder
+            .vsync()
+            .resizable();
+    } else {
+        // No vsync nor target FPS : frames are rendered as fast as ffmpeg can encode them
+        builder.resizable();
+    }
+    let (mut rl, thread) = builder.build();
+    if settings.preview {
+        rl.set_target_fps(config.fps);
+    }
     rl.set_trace_log(TraceLogLevel::LOG_ERROR);
 
-    let mut framebuffer = rl.load_render_texture(&thread, RENDER_WIDTH as u32, RENDER_HEIGHT as u32)
+    let mut framebuffer = rl.load_render_texture(&thread, config.width as u32, config.height as u32)
         .expect("Failed to create render frame buffer");
 
     // --- Load Slides ---
-    let image_paths = match load_sorted_image_paths(image_directory_path.to_str().unwrap()) {
-        Ok(paths) => paths,
+    let image_paths = match scan_images(&settings.sources, &settings.scan) {
+        Ok(images) => sort_images(images, settings.sort, config.seed, &settings.clock_offsets),
         Err(e) => {
-            display_error(&mut rl, &thread, &format!("Error loading images from '{}': {}", image_directory_path.to_str().unwrap(), e));
-            return;
+            display_error(&mut rl, &thread, settings.headless, &format!("Error loading images: {}", e));
+            return ExitCode::FAILURE;
         }
     };
+    eprintln!("{} image(s) found", image_paths.len());
 
     // keep first 5 images for testing
     // let image_paths = image_paths.into_iter().take(5).collect::<Vec<_>>();
 
-    let mut engine: Box<dyn Engine> = match args.engine.as_str() {
-        "spiral" => Box::new(SpiralEngine::new()),
-        "push-box" => Box::new(PushBoxEngine::new()),
-        _ => {
-            display_error(&mut rl, &thread, "Invalid engine specified.");
-            return;
+    let slide_sources = match load_slide_sources(image_paths, &settings.slides, &settings.clock_offsets) {
+        Ok(slide_sources) => slide_sources,
+        Err(e) => {
+            display_error(&mut rl, &thread, settings.headless, &e);
+            return ExitCode::FAILURE;
         }
     };
-    
-    if !engine.initialize(&mut rl, &thread, image_paths) {
-        display_error(&mut rl, &thread, "No slides were created successfully.");
-        return;
+
+    // The engine name was checked with the settings
+    let engine_info = find_engine(&settings.engine).expect("unknown engine");
+    let mut engine = (engine_info.create)(config.clone(), &settings.engine_options);
+
+    if !engine.initialize(&mut rl, &thread, slide_sources) {
+        display_error(&mut rl, &thread, settings.headless, "No slides were created successfully.");
+        return ExitCode::FAILURE;
     }
 
-    let mut ffmpeg = Ffmpeg::new(RENDER_WIDTH, RENDER_HEIGHT, FPS, &video_name);
+    if settings.preview {
+        run_preview(&mut rl, &thread, &settings, engine.as_ref(), &mut framebuffer);
+        return ExitCode::SUCCESS;
+    }
 
-    // --- Main Loop ---
-    while !rl.window_should_close() {
-        // let dt = rl.get_frame_time(); // realtime rendering
-        let dt = FRAME_TIME;
+    if settings.benchmark {
+        return benchmark(&mut rl, &thread, &settings, engine.as_ref(), &mut framebuffer);
+    }
 
-        if !engine.render_frame(dt, &mut rl, &thread, &mut framebuffer) {
-            drop(ffmpeg);
-            break;
-        }
+    let total_frames = config.frame_count(engine.total_duration());
+    let frames = settings.frames.clone().unwrap_or(0..u64::MAX);
+    let frames = frames.start..frames.end.min(total_frames);
+    if frames.is_empty() {
+        eprintln!("Error: Nothing to render, the slideshow is only {:.1}s long", engine.total_duration());
+        return ExitCode::FAILURE;
+    }
+    // Ctrl-C stops the render like closing the window: what was rendered is still written
+    if let Err(e) = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::Relaxed)) {
+        eprintln!("Warning: Ctrl-C will not finalize the video: {}", e);
+    }
 
-        // Draw inverted copy of framebuffer to the screen for feedback
+    // Segments of a single process render, for the parent process of --jobs
+    if let Some(chunk) = settings.chunk {
+        return match render_chunk(&mut rl, &thread, &settings, engine.as_ref(), &mut framebuffer, chunk, frames) {
+            Ok(true) => ExitCode::SUCCESS,
+            Ok(false) => ExitCode::FAILURE, // interrupted by the parent process
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let Some(pattern) = &settings.png else {
+        let result = render_video(&mut rl, &thread, &settings, engine.as_ref(), &mut framebuffer, frames);
+        return report_video(&settings, result);
+    };
+
+    // PNG files
+    let frames_to_render = frames.end - frames.start;
+    let mut readback = Readback::Sync;
+    let mut writer = FrameWriter::png(pattern, frames.start);
+
+    eprintln!("Rendering {} frames ({:.1}s)", frames_to_render, frames_to_render as f32 * config.frame_time());
+    let mut progress = Progress::new(settings.progress, frames_to_render);
+
+    let result = render(&mut rl, &thread, &settings, engine.as_ref(), &mut framebuffer, &mut readback, &mut writer, &mut progress, frames)
+        .and_then(|(frame, completed)| writer.finish().map(|_| (frame, completed)));
+    let (frame, completed) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    progress.finish(frame);
 
-        let mut d = rl.begin_drawing(&thread);
-        
-        let sw = d.get_screen_width() as f32;
-        let sh = d.get_screen_height() as f32;
-
-        d.draw_texture_pro(
-            &framebuffer,
-            Rectangle::new(0.0, 0.0, framebuffer.width() as f32, -(framebuffer.height() as f32)),
-            Rectangle::new(0.0, 0.0, sw, sh),
-            Vector2::new(0.0, 0.0),
-            0.0,
-            Color::WHITE
-        );
-
-        // Grab rendered texture pixels as an Image
-        let image = &framebuffer.load_image().expect("Failed to load image from framebuffer");
-
-        // Write the image to the ffmpeg pipe
-        ffmpeg.write(&image);
-    } // End main loop
-} // End main
\ No newline at end of file
+    eprintln!("{} frame(s) written to {}", frame, pattern);
+    if completed { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+} // End main
@@ -0,0 +1,120 @@
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_OUTPUT_TEMPLATE: &str = "{dir}.{ext}";
+
+/// Replaces the `{name}` placeholders of an output file name template, e.g.
+/// `{dir}_{engine}_{date}.{ext}` with the given values.
+pub fn expand_template(template: &str, values: &[(&str, String)]) -> Result<PathBuf, String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let end = rest[start..].find('}')
+            .ok_or_else(|| format!("Output template '{}': unclosed '{{'", template))?;
+        let name = &rest[start + 1..start + end];
+        let value = values.iter().find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| format!(
+                "Output template '{}': unknown placeholder '{{{}}}' (expected one of: {})",
+                template,
+                name,
+                values.iter().map(|(key, _)| format!("{{{}}}", key)).collect::<Vec<_>>().join(", ")
+            ))?;
+        output.push_str(value);
+        rest = &rest[start + end + 1..];
+    }
+    output.push_str(rest);
+
+    Ok(PathBuf::from(output))
+}
+
+/// The video is encoded to `<name>.partial.<ext>` next to the final file, then renamed on success
+pub fn partial_path(output: &Path) -> PathBuf {
+    let stem = output.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let name = match output.extension() {
+        Some(extension) => format!("{}.partial.{}", stem, extension.to_string_lossy()),
+        None => format!("{}.partial", stem),
+    };
+    output.with_file_name(name)
+}
+
+/// Segment `index` of a video rendered in pieces: `<name>.segment-<index>.mkv` next to the final
+/// file. Matroska holds any codec and segments are joined without re-encoding.
+pub fn segment_path(output: &Path, index: usize) -> PathBuf {
+    let stem = output.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    output.with_file_name(format!("{}.segment-{}.mkv", stem, index))
+}
+
+pub fn check_overwrite(output: &Path, force: bool) -> Result<(), String> {
+    if output.exists() && !force {
+        return Err(format!("Output file {} already exists (use --force to overwrite it)", output.display()));
+    }
+    Ok(())
+}
+
+/// Renames the complete video to the output file. The output is checked again since a file
+/// may have been created there during a long render, the video is then left in `partial`.
+pub fn finalize_output(partial: &Path, output: &Path, force: bool) -> Result<(), String> {
+    check_overwrite(output, force)
+        .map_err(|e| format!("{}, the video was kept as {}", e, partial.display()))?;
+    std::fs::rename(partial, output)
+        .map_err(|e| format!("Failed to rename {} to {}: {}", partial.display(), output.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> Vec<(&'static str, String)> {
+        vec![("dir", "Family".to_string()), ("engine", "spiral".to_string()), ("ext", "mp4".to_string())]
+    }
+
+    #[test]
+    fn template_placeholders_are_replaced() {
+        assert_eq!(expand_template("{dir}_{engine}.{ext}", &values()).unwrap(), PathBuf::from("Family_spiral.mp4"));
+        assert_eq!(expand_template("out/{dir}/{dir}.{ext}", &values()).unwrap(), PathBuf::from("out/Family/Family.mp4"));
+        assert_eq!(expand_template("video.mkv", &values()).unwrap(), PathBuf::from("video.mkv"));
+    }
+
+    #[test]
+    fn template_errors_are_reported() {
+        let error = expand_template("{dir}_{size}.{ext}", &values()).unwrap_err();
+        assert!(error.contains("unknown placeholder '{size}'"), "{}", error);
+        assert!(error.contains("{dir}, {engine}, {ext}"), "{}", error);
+
+        let error = expand_template("{dir.{ext}", &values()).unwrap_err();
+        assert!(error.contains("unknown placeholder '{dir.{ext}'"), "{}", error);
+
+        let error = expand_template("{dir}_{ext", &values()).unwrap_err();
+        assert!(error.contains("unclosed '{'"), "{}", error);
+    }
+
+    #[test]
+    fn partial_and_segment_files_are_next_to_the_output() {
+        assert_eq!(partial_path(Path::new("out/show.mp4")), PathBuf::from("out/show.partial.mp4"));
+        assert_eq!(partial_path(Path::new("show")), PathBuf::from("show.partial"));
+        assert_eq!(segment_path(Path::new("out/show.mp4"), 3), PathBuf::from("out/show.segment-3.mkv"));
+    }
+
+    #[test]
+    fn output_created_during_the_render_is_not_overwritten() {
+        let dir = std::env::temp_dir().join(format!("slideshow-test-{}-finalize", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("show.mp4");
+        let partial = partial_path(&output);
+        std::fs::write(&partial, "new").unwrap();
+        std::fs::write(&output, "old").unwrap();
+
+        let error = finalize_output(&partial, &output, false).unwrap_err();
+        assert!(error.contains("already exists"), "{}", error);
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "old");
+        assert!(partial.exists());
+
+        finalize_output(&partial, &output, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "new");
+        assert!(!partial.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+use std::sync::mpsc::{channel, sync_channel, Receiver, SyncSender};
+use std::thread::JoinHandle;
+use anyhow::{anyhow, bail, Result};
+use raylib::prelude::*;
+use crate::ffmpeg::Ffmpeg;
+use crate::output::expand_template;
+
+/// Copies a frame read back from the GPU, `width` RGBA pixels per row, into `buffer`, top row
+/// first as ffmpeg expects it (OpenGL frame buffers are stored bottom to top)
+pub fn flip_into(pixels: &[u8], width: usize, buffer: &mut Vec<u8>) {
+    let row_len = width * 4; // 4 bytes per pixel (RGBA)
+
+    buffer.clear();
+    for row in pixels.chunks_exact(row_len).rev() {
+        buffer.extend_from_slice(row);
+    }
+}
+
+/// Sends rendered frames to ffmpeg, or to PNG files.
+///
+/// With a pipeline depth of 0 frames are written on the render thread. Otherwise they are
+/// queued to a writer thread, so the next frames are rendered while ffmpeg encodes the
+/// previous ones. Frame buffers are recycled, at most `depth + 2` of them are allocated.
+pub enum FrameWriter {
+    Png {
+        pattern: String, // file name, `{frame}` is replaced by the frame number
+        frame: u64,
+    },
+    Direct {
+        ffmpeg: Option<Ffmpeg>,
+        buffer: Vec<u8>,
+    },
+    Pipelined {
+        frames: Option<SyncSender<Vec<u8>>>,
+        free: Receiver<Vec<u8>>,
+        allocated: usize,
+        max_buffers: usize,
+        writer: Option<JoinHandle<Result<Ffmpeg>>>,
+    },
+}
+
+impl FrameWriter {
+    pub fn new(ffmpeg: Ffmpeg, depth: usize) -> FrameWriter {
+        if depth == 0 {
+            return FrameWriter::Direct { ffmpeg: Some(ffmpeg), buffer: Vec::new() };
+        }
+
+        let (frames_tx, frames_rx) = sync_channel::<Vec<u8>>(depth);
+        let (free_tx, free_rx) = channel::<Vec<u8>>();
+
+        let writer = std::thread::spawn(move || {
+            let mut ffmpeg = ffmpeg;
+            for frame in frames_rx {
+                ffmpeg.write(&frame)?;
+                let _ = free_tx.send(frame); // give the buffer back for another frame
+            }
+            Ok(ffmpeg)
+        });
+
+        FrameWriter::Pipelined {
+            frames: Some(frames_tx),
+            free: free_rx,
+            allocated: 0,
+            max_buffers: depth + 2, // queued frames + the one being written + the one being filled
+            writer: Some(writer),
+        }
+    }
+
+    /// Writes frames to `pattern`, numbered from `first_frame`
+    pub fn png(pattern: &str, first_frame: u64) -> FrameWriter {
+        FrameWriter::Png { pattern: pattern.to_string(), frame: first_frame }
+    }
+
+    /// Writes a frame read back from the GPU: `width` RGBA pixels per row, bottom row first
+    pub fn write(&mut self, pixels: &[u8], width: usize) -> Result<()> {
+        match self {
+            FrameWriter::Png { pattern, frame } => {
+                let path = expand_template(pattern, &[("frame", format!("{:06}", frame))])
+                    .map_err(|e| anyhow!(e))?;
+                let height = pixels.len() / (width * 4);
+                let mut image = Image::gen_image_color(width as i32, height as i32, Color::BLACK);
+                unsafe { std::ptr::copy_nonoverlapping(pixels.as_ptr(), image.data() as *mut u8, width * height * 4) };
+                image.flip_vertical();
+                if !image.export_image(&path.to_string_lossy()) {
+                    bail!("Failed to write {}", path.display());
+                }
+                *frame += 1;
+                Ok(())
+            }
+            FrameWriter::Direct { ffmpeg, buffer } => {
+                let ffmpeg = ffmpeg.as_mut().ok_or_else(|| anyhow!("ffmpeg input is already closed"))?;
+                flip_into(pixels, width, buffer);
+                ffmpeg.write(buffer)
+            }
+            FrameWriter::Pipelined { frames, free, allocated, max_buffers, writer } => {
+                let mut buffer = match free.try_recv() {
+                    Ok(buffer) => buffer,
+                    Err(_) if *allocated < *max_buffers => {
+                        *allocated += 1;
+                        Vec::new()
+                    }
+                    // Wait for the writer thread to be done with a frame
+                    Err(_) => match free.recv() {
+                        Ok(buffer) => buffer,
+                        Err(_) => return Err(Self::writer_error(frames, writer)),
+                    },
+                };
+
+                flip_into(pixels, width, &mut buffer);
+
+                let sent = frames.as_ref().is_some_and(|frames| frames.send(buffer).is_ok());
+                if !sent {
+                    // The writer thread stopped, it knows why
+                    return Err(Self::writer_error(frames, writer));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Waits for the queued frames to be encoded, then for ffmpeg to finalize the video
+    pub fn finish(mut self) -> Result<()> {
+        let ffmpeg = match &mut self {
+            FrameWriter::Png { .. } => return Ok(()),
+            FrameWriter::Direct { ffmpeg, .. } => {
+                ffmpeg.take().ok_or_else(|| anyhow!("ffmpeg input is already closed"))?
+            }
+            FrameWriter::Pipelined { frames, writer, .. } => {
+                frames.take(); // end of the stream for the writer thread
+                Self::join(writer)?
+            }
+        };
+        ffmpeg.finish()
+    }
+
+    fn join(writer: &mut Option<JoinHandle<Result<Ffmpeg>>>) -> Result<Ffmpeg> {
+        let writer = writer.take().ok_or_else(|| anyhow!("ffmpeg writer thread already stopped"))?;
+        writer.join().map_err(|_| anyhow!("ffmpeg writer thread panicked"))?
+    }
+
+    fn writer_error(frames: &mut Option<SyncSender<Vec<u8>>>, writer: &mut Option<JoinHandle<Result<Ffmpeg>>>) -> anyhow::Error {
+        frames.take();
+        match Self::join(writer) {
+            Ok(_) => anyhow!("ffmpeg writer thread stopped unexpectedly"),
+            Err(e) => e,
+        }
+    }
+}
+
+impl Drop for FrameWriter {
+    fn drop(&mut self) {
+        // Let the writer thread drain its queue and close ffmpeg
+        if let FrameWriter::Pipelined { frames, writer, .. } = self {
+            frames.take();
+            let _ = Self::join(writer);
+        }
+    }
+}
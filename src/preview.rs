@@ -0,0 +1,124 @@
+use raylib::prelude::*;
+use crate::cli::Settings;
+use crate::engine::Engine;
+
+const MIN_SPEED: f32 = 0.125;
+const MAX_SPEED: f32 = 8.0;
+const MAX_FRAME_TIME: f32 = 0.1; // don't jump ahead after a hiccup (window moved...)
+const HUD_FONT_SIZE: i32 = 20;
+
+const HELP: &str = "Space: pause  Left/Right: step  R: restart  +/-: speed  digits + Enter: go to slide  H: hide";
+
+fn format_time(seconds: f32) -> String {
+    let seconds = seconds.max(0.0);
+    format!("{:02}:{:04.1}", (seconds / 60.0) as u32, seconds % 60.0)
+}
+
+/// Plays the slideshow in real time in the window, without encoding.
+pub fn run_preview(rl: &mut RaylibHandle, thread: &RaylibThread, settings: &Settings, engine: &dyn Engine, framebuffer: &mut RenderTexture2D) {
+    let frame_time = settings.config.frame_time();
+    let total_duration = engine.total_duration();
+
+    let mut time: f32 = 0.0;
+    let mut paused = false;
+    let mut speed: f32 = 1.0;
+    let mut show_hud = true;
+    let mut slide_input = String::new();
+
+    while !rl.window_should_close() {
+        // --- Keyboard ---
+        if rl.is_key_pressed(KeyboardKey::KEY_SPACE) {
+            paused = !paused;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_RIGHT) || rl.is_key_pressed(KeyboardKey::KEY_PERIOD) {
+            paused = true;
+            time += frame_time;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_LEFT) || rl.is_key_pressed(KeyboardKey::KEY_COMMA) {
+            paused = true;
+            time -= frame_time;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_R) {
+            time = 0.0;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_EQUAL) || rl.is_key_pressed(KeyboardKey::KEY_KP_ADD) {
+            speed = (speed * 2.0).min(MAX_SPEED);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_MINUS) || rl.is_key_pressed(KeyboardKey::KEY_KP_SUBTRACT) {
+            speed = (speed / 2.0).max(MIN_SPEED);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_H) {
+            show_hud = !show_hud;
+        }
+        while let Some(c) = rl.get_char_pressed() {
+            if c.is_ascii_digit() {
+                slide_input.push(c);
+            }
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
+            slide_input.pop();
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+            // Slides are numbered from 1 on screen
+            if let Ok(slide) = slide_input.parse::<usize>() {
+                let index = slide.saturating_sub(1).min(engine.slide_count().saturating_sub(1));
+                time = engine.slide_start(index);
+            }
+            slide_input.clear();
+        }
+
+        // --- Playback ---
+        if !paused {
+            time += rl.get_frame_time().min(MAX_FRAME_TIME) * speed;
+        }
+        time = time.clamp(0.0, total_duration);
+        let finished = time >= total_duration;
+
+        engine.render_at(time, rl, thread, framebuffer);
+
+        // --- Display ---
+        let mut d = rl.begin_drawing(thread);
+        d.clear_background(Color::BLACK);
+
+        let sw = d.get_screen_width() as f32;
+        let sh = d.get_screen_height() as f32;
+
+        d.draw_texture_pro(
+            &*framebuffer,
+            Rectangle::new(0.0, 0.0, framebuffer.width() as f32, -(framebuffer.height() as f32)),
+            Rectangle::new(0.0, 0.0, sw, sh),
+            Vector2::new(0.0, 0.0),
+            0.0,
+            Color::WHITE
+        );
+
+        if show_hud {
+            let status = if finished {
+                "FINISHED"
+            } else if paused {
+                "PAUSED"
+            } else {
+                ""
+            };
+            let (slide, state) = engine.slide_at(time);
+            let line = format!(
+                "Slide {}/{}  {}  {} / {}  x{}  {}",
+                slide + 1,
+                engine.slide_count(),
+                state,
+                format_time(time),
+                format_time(total_duration),
+                speed,
+                status,
+            );
+            let line_height = HUD_FONT_SIZE + 8;
+            let lines = if slide_input.is_empty() { 2 } else { 3 };
+            d.draw_rectangle(0, 0, sw as i32, line_height * lines + 8, Color::new(0, 0, 0, 160));
+            d.draw_text(&line, 10, 10, HUD_FONT_SIZE, Color::WHITE);
+            d.draw_text(HELP, 10, 10 + line_height, HUD_FONT_SIZE, Color::LIGHTGRAY);
+            if !slide_input.is_empty() {
+                d.draw_text(&format!("Go to slide: {}_", slide_input), 10, 10 + line_height * 2, HUD_FONT_SIZE, Color::YELLOW);
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::io::Write;
+use std::time::{Duration, Instant};
+use serde_json::json;
+
+const BAR_WIDTH: usize = 30;
+const BAR_REFRESH: Duration = Duration::from_millis(250);
+const JSON_REFRESH: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressMode {
+    Bar,  // human readable progress bar on stderr
+    Json, // one JSON object per line on stdout, for scripts
+}
+
+/// Reports how far along a render is: frames, elapsed time, encoding speed and ETA
+pub struct Progress {
+    mode: ProgressMode,
+    total_frames: u64,
+    frame: u64,
+    start: Instant,
+    last_report: Option<Instant>,
+}
+
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.max(0.0).round() as u64;
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+impl Progress {
+    pub fn new(mode: ProgressMode, total_frames: u64) -> Self {
+        Self {
+            mode,
+            total_frames: total_frames.max(1),
+            frame: 0,
+            start: Instant::now(),
+            last_report: None,
+        }
+    }
+
+    /// When the number of frames is only known once the render started
+    pub fn set_total_frames(&mut self, total_frames: u64) {
+        self.total_frames = total_frames.max(1);
+    }
+
+    /// Last frame count reported
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Called after each encoded frame, output is throttled
+    pub fn update(&mut self, frame: u64) {
+        self.frame = frame;
+        let refresh = match self.mode {
+            ProgressMode::Bar => BAR_REFRESH,
+            ProgressMode::Json => JSON_REFRESH,
+        };
+        if self.last_report.is_some_and(|last| last.elapsed() < refresh) {
+            return;
+        }
+        self.last_report = Some(Instant::now());
+        self.report("progress", frame);
+    }
+
+    pub fn finish(&mut self, frame: u64) {
+        self.frame = frame;
+        self.total_frames = self.total_frames.max(frame);
+        self.report("done", frame);
+        if self.mode == ProgressMode::Bar {
+            eprintln!();
+        }
+    }
+
+    fn report(&self, event: &str, frame: u64) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let fps = if elapsed > 0.0 { frame as f64 / elapsed } else { 0.0 };
+        // The frame count is an estimate, never report more than 100%
+        let ratio = (frame as f64 / self.total_frames as f64).min(1.0);
+        let remaining = self.total_frames.saturating_sub(frame);
+        let eta = if fps > 0.0 { remaining as f64 / fps } else { 0.0 };
+
+        match self.mode {
+            ProgressMode::Bar => {
+                let filled = (ratio * BAR_WIDTH as f64) as usize;
+                eprint!(
+                    "\r[{}{}] {:3.0}% {}/{} frames | {} elapsed | {:.1} fps | ETA {} ",
+                    "#".repeat(filled),
+                    "-".repeat(BAR_WIDTH - filled),
+                    ratio * 100.0,
+                    frame,
+                    self.total_frames,
+                    format_duration(elapsed),
+                    fps,
+                    format_duration(eta),
+                );
+                let _ = std::io::stderr().flush();
+            }
+            ProgressMode::Json => {
+                println!("{}", json!({
+                    "event": event,
+                    "frame": frame,
+                    "total_frames": self.total_frames,
+                    "percent": (ratio * 1000.0).round() / 10.0,
+                    "elapsed": (elapsed * 10.0).round() / 10.0,
+                    "fps": (fps * 10.0).round() / 10.0,
+                    "eta": eta.round(),
+                }));
+                let _ = std::io::stdout().flush();
+            }
+        }
+    }
+}
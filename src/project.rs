@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use crate::clock::ClockOffset;
+use crate::encoder::EncoderOverrides;
+use crate::engine::{engine_names, find_engine};
+use crate::slide_settings::SlideSettings;
+use crate::sort::SortOrder;
+use crate::timing::TimingOverrides;
+
+/// Declarative description of a whole render, read from a TOML project file:
+///
+/// ```toml
+/// engine = "push-box"
+/// sources = ["Family"]
+/// recursive = true
+/// exclude = ["**/thumbnails/*"]
+/// sort = "exif-date"
+/// output = "family.mp4"
+/// width = 1920
+/// height = 1080
+/// fps = 60
+/// seed = 1234
+/// music = "soundtrack.mp3"
+///
+/// [encoder]
+/// preset = "archive"
+/// crf = 20
+///
+/// [timing]
+/// preset = "memorial"
+///
+/// [engines.push-box]
+/// direction = "right-to-left"
+///
+/// [[clock_offsets]]
+/// model = "Pixel 7"
+/// offset = "-00:42"
+///
+/// [slides."Family/IMG_0042.jpg"]
+/// hero = true
+/// caption = "Grandma's 90th birthday"
+/// ```
+///
+/// Relative paths are resolved against the directory of the project file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Project {
+    pub engine: Option<String>,
+    #[serde(default)]
+    pub sources: Vec<PathBuf>,
+    #[serde(default)]
+    pub recursive: bool,
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    pub sort: Option<SortOrder>,
+    pub output: Option<PathBuf>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub fps: Option<u32>,
+    pub seed: Option<u64>,
+    pub music: Option<PathBuf>,
+    /// Deprecated: moved to `[encoder] codec`, still read from older project files
+    pub codec: Option<String>,
+    pub encoder: Option<EncoderOverrides>,
+    pub timing: Option<TimingOverrides>,
+    /// `[engines.<name>]` tables, read by each engine
+    #[serde(default)]
+    pub engines: BTreeMap<String, toml::Table>,
+    /// Camera clock corrections, applied to the capture dates
+    #[serde(default)]
+    pub clock_offsets: Vec<ClockOffset>,
+    /// Per-slide overrides, keyed by image path (canonical once loaded)
+    #[serde(default)]
+    pub slides: BTreeMap<PathBuf, SlideSettings>,
+    /// Directory of the project file, engine options are relative to it
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+impl Project {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read project file {}: {}", path.display(), e))?;
+        let mut project: Project = toml::from_str(&content)
+            .map_err(|e| format!("Invalid project file {}: {}", path.display(), e))?;
+
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        project.base_dir = base_dir.to_path_buf();
+        project.migrate()
+            .and_then(|_| project.resolve_paths(base_dir))
+            .and_then(|_| project.validate())
+            .map_err(|e| format!("Invalid project file {}: {}", path.display(), e))?;
+
+        Ok(project)
+    }
+
+    /// Moves the keys of older project files to their current place
+    fn migrate(&mut self) -> Result<(), String> {
+        if let Some(codec) = self.codec.take() {
+            eprintln!("Warning: the top-level 'codec' key of project files is deprecated, use 'codec' in the [encoder] table");
+            let encoder = self.encoder.get_or_insert_default();
+            match &encoder.codec {
+                Some(encoder_codec) if *encoder_codec != codec => {
+                    return Err(format!("codec: '{}' conflicts with [encoder] codec '{}'", codec, encoder_codec));
+                }
+                _ => encoder.codec = Some(codec),
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_paths(&mut self, base_dir: &Path) -> Result<(), String> {
+        for source in self.sources.iter_mut() {
+            *source = base_dir.join(&source);
+        }
+        if let Some(output) = self.output.as_mut() {
+            *output = base_dir.join(&output);
+        }
+        if let Some(music) = self.music.as_mut() {
+            *music = base_dir.join(&music);
+        }
+
+        // Photos are matched against clock offset directories by canonical path too
+        for clock_offset in self.clock_offsets.iter_mut() {
+            if let Some(directory) = clock_offset.directory.as_mut() {
+                *directory = base_dir.join(&directory).canonicalize()
+                    .map_err(|e| format!("clock_offsets: '{}': {}", directory.display(), e))?;
+            }
+        }
+
+        // Slides are matched against scanned images by canonical path
+        let slides = std::mem::take(&mut self.slides);
+        for (slide_path, settings) in slides {
+            let canonical_path = base_dir.join(&slide_path).canonicalize()
+                .map_err(|e| format!("slides: '{}': {}", slide_path.display(), e))?;
+            self.slides.insert(canonical_path, settings);
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for source in self.sources.iter() {
+            if !source.is_dir() {
+                return Err(format!("sources: '{}' is not a directory", source.display()));
+            }
+        }
+        if let Some(music) = &self.music {
+            if !music.is_file() {
+                return Err(format!("music: '{}' does not exist", music.display()));
+            }
+        }
+        if let Some(name) = self.engines.keys().find(|name| find_engine(name).is_none()) {
+            return Err(format!("engines: unknown engine '{}' (expected one of: {})", name, engine_names()));
+        }
+        for clock_offset in self.clock_offsets.iter() {
+            clock_offset.validate().map_err(|e| format!("clock_offsets: {}", e))?;
+        }
+        for (slide_path, settings) in self.slides.iter() {
+            settings.validate()
+                .map_err(|e| format!("slides: '{}': {}", slide_path.display(), e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_level_codec_moves_to_the_encoder_table() {
+        let mut project: Project = toml::from_str("codec = \"libx265\"").unwrap();
+        project.migrate().unwrap();
+        assert_eq!(project.codec, None);
+        assert_eq!(project.encoder.unwrap().codec.as_deref(), Some("libx265"));
+    }
+
+    #[test]
+    fn top_level_codec_conflicting_with_the_encoder_table_is_rejected() {
+        let mut project: Project = toml::from_str("codec = \"libx265\"\n[encoder]\ncodec = \"libx264\"").unwrap();
+        assert!(project.migrate().is_err());
+
+        let mut project: Project = toml::from_str("codec = \"libx264\"\n[encoder]\ncodec = \"libx264\"").unwrap();
+        assert!(project.migrate().is_ok());
+    }
+}
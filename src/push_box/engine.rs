@@ -1,32 +1,53 @@
-use std::path::PathBuf;
 use raylib::prelude::*;
 use crate::load_texture_with_exif_rotation;
 use crate::push_box::slide::Slide;
-use crate::push_box::state::PushBoxState;
 use crate::subject_detection::DetectionModel;
+use crate::config::RenderConfig;
+use crate::slide_settings::SlideSource;
+use crate::timeline::{index_at, start_times};
+use crate::engine::{resolve_options, Engine, EngineInfo};
+use crate::push_box::options::{PushBoxOptions, PushBoxOverrides};
 
 pub struct PushBoxEngine {
+    config: RenderConfig,
+    options: PushBoxOptions,
     slides: Vec<Slide>,
-    current_slide_index: usize,
+    starts: Vec<f32>, // time each slide starts entering
 }
 
-impl crate::engine::Engine for PushBoxEngine {
-    fn new() -> Self {
+impl PushBoxEngine {
+    pub const INFO: EngineInfo = EngineInfo {
+        name: "push-box",
+        description: "Photos are pushed across the screen one after the other, zoomed in on their subject (Ken Burns effect)",
+        options: <PushBoxOverrides as clap::Args>::augment_args,
+        resolve_options: resolve_options::<PushBoxOptions>,
+        uses: |_| Vec::new(),
+        create: |config, options| Box::new(PushBoxEngine::new(config, *options.get::<PushBoxOptions>())),
+    };
+
+    pub fn new(config: RenderConfig, options: PushBoxOptions) -> Self {
         Self {
+            config,
+            options,
             slides: Vec::new(),
-            current_slide_index: 0,
+            starts: Vec::new(),
         }
     }
+}
 
-    fn initialize(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, paths: Vec<PathBuf>) -> bool {
+impl Engine for PushBoxEngine {
+    fn initialize(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, sources: Vec<SlideSource>) -> bool {
         let mut detection_model = DetectionModel::new(vec![0]).unwrap();
 
-        for path in paths {
+        for SlideSource { path, settings } in sources {
             match load_texture_with_exif_rotation(rl, thread, &path) {
                 Ok(image) => {
-                    let detections = detection_model.detect(&path).unwrap();
-                   
-                    println!("{}: {:?}", path.to_str().unwrap(), detections);
+                    // A manual focus point makes subject detection useless
+                    let detections = if settings.focus.is_some() {
+                        Vec::new()
+                    } else {
+                        detection_model.detect(&path).unwrap()
+                    };
 
                     let merged_box = if !detections.is_empty() {
                         // merge all boxes into one raylib Rectangle
@@ -45,8 +66,6 @@ impl crate::engine::Engine for PushBoxEngine {
                         // Convert absolute width and height to relative values
                         merged_box.width = merged_box.width - merged_box.x;
                         merged_box.height = merged_box.height - merged_box.y;
-                        
-                        println!("Merged Box: {:?}", merged_box);
                         merged_box
                     } else {
                         Rectangle::new(0.0, 0.0, 0.0, 0.0)
@@ -93,76 +112,51 @@ impl crate::engine::Engine for PushBoxEngine {
                     // let tmp_texture = rl.load_texture_from_image(&thread, &tmp_image)
                     //     .expect("Failed to create texture from image");
 
-                    self.slides.push(Slide::new(image, merged_box));
+                    self.slides.push(Slide::new(image, merged_box, &self.config, &self.options, &settings));
                 }
                 Err(e) => {
-                    println!("Failed to load image: {}", e);
+                    eprintln!("Failed to load image: {}", e);
                 }
             }
         }
 
-        // If there are slides, start the first one
-        if !self.slides.is_empty() {
-            self.slides[0].visible = true;
-            self.slides[0].is_animating = true;
-            return true;
-        }
+        // Slides overlap: the next slide enters while the current one exits
+        self.starts = start_times(self.slides.iter().map(|slide| slide.duration_until_exit()));
 
-        false
+        !self.slides.is_empty()
     }
 
-    fn render_frame(&mut self, dt: f32, rl: &mut RaylibHandle, thread: &RaylibThread, framebuffer: &mut RenderTexture2D) -> bool {      
-
-        // Iterate only current, previous and next slides
-        let mut slides_to_update = vec![self.current_slide_index];
-        if self.current_slide_index > 0 {
-            slides_to_update.push(self.current_slide_index - 1);
-        }
-        if self.current_slide_index < self.slides.len() - 1 {
-            slides_to_update.push(self.current_slide_index + 1);
-        }
-
-        let mut is_animating = false;
-        let mut animate_next_slide = false;
-
-        for slide_index in slides_to_update.iter() {
-            let slide = &mut self.slides[*slide_index];
-
-            let slide_state_before = slide.state.clone();
-            slide.update(dt);
-            let slide_state_after = slide.state.clone();
-
-            // If current slide is exiting, move to next slide
-            if *slide_index == self.current_slide_index && slide_state_before == PushBoxState::ZoomingOut && slide_state_after == PushBoxState::Exiting {
-                animate_next_slide = true;
-                is_animating = true;
-            }    
+    fn total_duration(&self) -> f32 {
+        let slides: f32 = self.slides.iter().map(|slide| slide.duration_until_exit()).sum();
+        slides + self.slides.last().map_or(0.0, |slide| slide.exit_duration())
+    }
 
-            is_animating = slide.is_animating || is_animating;
-        }
+    fn slide_count(&self) -> usize {
+        self.slides.len()
+    }
 
-        if !is_animating {
-            return false;
-        }
+    fn slide_start(&self, index: usize) -> f32 {
+        self.starts.get(index).copied().unwrap_or(0.0)
+    }
 
-        // Activate next slide
-        if animate_next_slide && self.current_slide_index < self.slides.len() - 1 {
-            self.current_slide_index += 1;
-            self.slides[self.current_slide_index].visible = true;
-            self.slides[self.current_slide_index].is_animating = true;
-        }
+    fn slide_at(&self, time: f32) -> (usize, String) {
+        // The last slide that entered, the previous one may still be exiting
+        let index = index_at(&self.starts, time);
+        let state = self.slides.get(index)
+            .and_then(|slide| slide.state_at(time - self.starts[index]))
+            .map_or("Finished".to_string(), |(state, _)| format!("{:?}", state));
+        (index, state)
+    }
 
-        rl.draw_texture_mode(thread, framebuffer,  |mut tmd| {
+    fn render_at(&self, time: f32, rl: &mut RaylibHandle, thread: &RaylibThread, framebuffer: &mut RenderTexture2D) {
+        rl.draw_texture_mode(thread, framebuffer, |mut tmd| {
             let mut d = tmd.begin_drawing(thread);
             d.clear_background(Color::BLACK);
-            
-            for slide in self.slides.iter() {
-                if slide.visible {
-                    slide.draw(&mut d);
-                }
-            }
-        });   
 
-        true
+            // Slides are drawn in order, the entering slide covers the exiting one
+            for (slide, start) in self.slides.iter().zip(self.starts.iter()) {
+                slide.draw(&mut d, &self.config, time - start);
+            }
+        });
     }
 }
@@ -1,3 +1,4 @@
 pub mod state;
 pub mod engine;
-pub mod slide;
\ No newline at end of file
+pub mod slide;
+pub mod options;
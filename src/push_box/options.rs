@@ -0,0 +1,71 @@
+use raylib::prelude::Vector2;
+use serde::Deserialize;
+use crate::engine::EngineOptionSet;
+
+/// Direction slides are pushed across the screen
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+    LeftToRight,
+    RightToLeft,
+    TopToBottom,
+    BottomToTop,
+}
+
+impl Direction {
+    /// Position on screen (relative) of a slide `progress` along its way: -0.5 is outside the
+    /// screen before entering, 0.5 the center and 1.5 outside after exiting
+    pub fn position(&self, progress: f32) -> Vector2 {
+        match self {
+            Direction::LeftToRight => Vector2::new(progress, 0.5),
+            Direction::RightToLeft => Vector2::new(1.0 - progress, 0.5),
+            Direction::TopToBottom => Vector2::new(0.5, progress),
+            Direction::BottomToTop => Vector2::new(0.5, 1.0 - progress),
+        }
+    }
+}
+
+/// Options of the push-box engine
+#[derive(Debug, Clone, Copy)]
+pub struct PushBoxOptions {
+    pub direction: Direction,
+    pub margin: f32, // part of the screen filled by photos with the same orientation as the output
+}
+
+impl Default for PushBoxOptions {
+    fn default() -> Self {
+        Self { direction: Direction::LeftToRight, margin: 0.9 }
+    }
+}
+
+// push-box options from the command line or the [engines.push-box] section of a project file.
+#[derive(Debug, Default, Clone, Deserialize, clap::Args)]
+#[command(next_help_heading = "push-box engine")]
+#[serde(deny_unknown_fields)]
+pub struct PushBoxOverrides {
+    #[arg(long = "push-box-direction", value_enum, help = "Direction slides are pushed across the screen [default: left-to-right]")]
+    pub direction: Option<Direction>,
+
+    #[arg(long = "push-box-margin", value_name = "FRACTION", help = "Part of the screen filled by photos with the same orientation as the video [default: 0.9]")]
+    pub margin: Option<f32>,
+}
+
+impl EngineOptionSet for PushBoxOptions {
+    type Overrides = PushBoxOverrides;
+
+    fn apply(&mut self, overrides: &PushBoxOverrides) {
+        if let Some(direction) = overrides.direction {
+            self.direction = direction;
+        }
+        if let Some(margin) = overrides.margin {
+            self.margin = margin;
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(self.margin > 0.0 && self.margin <= 1.0) {
+            return Err(format!("Invalid push-box margin {}: must be between 0.0 and 1.0", self.margin));
+        }
+        Ok(())
+    }
+}
@@ -1,87 +1,117 @@
 use raylib::prelude::*;
-use crate::constants::*;
+use crate::config::RenderConfig;
+use crate::timing::Timing;
 use crate::push_box::state::PushBoxState;
+use crate::push_box::options::{Direction, PushBoxOptions};
+use crate::slide_settings::{SlideSettings, Transition};
+use crate::caption::draw_caption;
+use crate::timeline::{phase_at, Track};
+
+/// Ken Burns effect of a `image_width` x `image_height` image covering `coverage` (0.0 - 1.0)
+/// of the output: final scale and pan (in pixels, relative to the image) towards the manual
+/// `focus` point, or else the detected subjects
+fn ken_burns_move(image_width: f32, image_height: f32, subject_rect: Rectangle, focus: Option<[f32; 2]>, coverage: f32) -> (f32, Vector2) {
+    // A manual focus point wins over the detected subjects, otherwise
+    // if no subject rect, use the whole image
+    let subject_rect = if let Some([focus_x, focus_y]) = focus {
+        Rectangle::new(
+            focus_x * image_width - image_width * 0.25,
+            focus_y * image_height - image_height * 0.25,
+            image_width * 0.5,
+            image_height * 0.5,
+        )
+    } else if subject_rect.width == 0.0 || subject_rect.height == 0.0 {
+        Rectangle::new(0.0, 0.0, image_width, image_height)
+    } else {
+        subject_rect
+    };
+
+    // Zoom-in to subject rect
+    let subject_size = subject_rect.width.max(subject_rect.height);
+    let image_size   = image_width.max(image_height);
+    let base_scale   = subject_size / image_size;
+
+    // Calculate the center of the bounding rectangle
+    let subject_center = Vector2::new(
+        subject_rect.x + subject_rect.width / 2.0,
+        subject_rect.y + subject_rect.height / 2.0,
+    );
+
+    // Define the final position of the move (in pixels, relative to the image)
+    let ken_burns_end_pos = Vector2::new(
+        subject_center.x - (image_width / 2.0),
+        subject_center.y - (image_height / 2.0),
+    );
+
+    // Pan distance relative to the image size on each axis so that portrait and
+    // landscape photos move at the same pace
+    let pan_factor = (ken_burns_end_pos.x.abs() / image_width)
+        .max(ken_burns_end_pos.y.abs() / image_height)
+        .max(1.0 / image_size);
+    // Base scale is clamped between 0.8 and 1.0 for photos filling the output,
+    // letterboxed photos are already small on screen so they get a gentler zoom
+    let min_ken_burns_scale = 0.8 + 0.1 * (1.0 - coverage.min(1.0));
+    let ken_burns_scale = (base_scale + pan_factor * 0.7).clamp(min_ken_burns_scale, 1.0);
+
+    (ken_burns_scale, ken_burns_end_pos)
+}
 
 pub struct Slide {
     pub image: Texture2D,
 
-    pub visible: bool,
-    pub state: PushBoxState,
+    timing: Timing, // engine timing with this slide's overrides applied
 
-    pub is_animating: bool,
-    animation_timer: f32,
+    transition: Transition,
+    direction: Direction,
+    caption: Option<String>,
 
     initial_scale: f32, // how the image appears from the left
     final_scale: f32,   // scale factor to fit the screen
 
-    // Current values during animation, those are computed by Tweens below
-    position: Vector2,
-    scale: f32,
-    
-    tween_entering: ease::Tween,
-    tween_zooming_in: ease::Tween,
-    tween_zooming_out: ease::Tween,
-    tween_exiting: ease::Tween,
-    
-    // Ken Burns effect parameters for Displaying state, those are computed by Tweens below
-    ken_burns_scale: f32,
-    ken_burns_pan: Vector2,
-
-    tween_ken_burns_scale: ease::Tween,
-    tween_ken_burns_pan_x: ease::Tween,
-    tween_ken_burns_pan_y: ease::Tween,
+    // Animations of each phase, evaluated at the time spent in the phase
+    track_entering: Track,
+    track_zooming_in: Track,
+    track_zooming_out: Track,
+    track_exiting: Track,
+
+    // Ken Burns effect during the Displaying phase
+    track_ken_burns_scale: Track,
+    track_ken_burns_pan_x: Track,
+    track_ken_burns_pan_y: Track,
 }
 
 impl Slide {
-    pub fn new(image: Texture2D, subject_rect: Rectangle) -> Self {
-        // Scale images too big to fit the screen
-        let final_scale = if image.width() > image.height() {
-            if image.width() as f32 > RENDER_WIDTH as f32 * 0.9 {
-                (RENDER_WIDTH as f32 * 0.9) / image.width() as f32
-            } else {
-                1.0
-            }
-        } else {
-            if image.height() as f32 > RENDER_HEIGHT as f32 * 0.9 {
-                (RENDER_HEIGHT as f32 * 0.9) / image.height() as f32
-            } else {
-                1.0
-            }
-        };
+    pub fn new(image: Texture2D, subject_rect: Rectangle, config: &RenderConfig, options: &PushBoxOptions, settings: &SlideSettings) -> Self {
+        let transition = settings.transition();
+
+        let mut timing = config.timing;
+        timing.displaying = settings.display_duration(timing.displaying);
+        if transition == Transition::Cut {
+            // Appear / disappear at full size, without any animation
+            timing.entering = 0.0;
+            timing.zooming_in = 0.0;
+            timing.zooming_out = 0.0;
+            timing.exiting = 0.0;
+        }
 
-        // Initial scale is half of final scale
-        let initial_scale = final_scale * 0.5;
+        let image_width = image.width() as f32;
+        let image_height = image.height() as f32;
 
-        /* Ken Burns effect parameters */
+        // Scale images too big to fit the screen. Photos with the same orientation as the
+        // output fill 90% of it, the others are letterboxed edge to edge on their long side.
+        let image_ratio = image_width / image_height;
+        let same_orientation = (image_ratio >= 1.0) == (config.aspect_ratio() >= 1.0);
+        let margin = if same_orientation { options.margin } else { 1.0 };
+        let final_scale = config.fit_scale(image_width, image_height, margin);
 
-        // If no subject rect, use the whole image
-        let subject_rect = if subject_rect.width == 0.0 || subject_rect.height == 0.0 {
-            Rectangle::new(0.0, 0.0, image.width() as f32, image.height() as f32)
-        } else {
-            subject_rect
-        };  
-
-        // Zoom-in to subject rect
-        let subject_size = subject_rect.width.max(subject_rect.height) as f32;
-        let image_size   = image.width().max(image.height()) as f32;
-        let base_scale   = subject_size / image_size;
-
-        // Calculate the center of the bounding rectangle
-        let subject_center = Vector2::new(
-            subject_rect.x + subject_rect.width / 2.0,
-            subject_rect.y + subject_rect.height / 2.0,
-        );
+        // How much of the output is covered by the photo once zoomed in (0.0 - 1.0)
+        let coverage = (image_width * final_scale * image_height * final_scale)
+            / (config.width as f32 * config.height as f32);
 
-        // Define the final position of the move (in pixels, relative to the image)
-        let ken_burns_end_pos = Vector2::new(
-            subject_center.x - (image.width() as f32 / 2.0),
-            subject_center.y - (image.height() as f32 / 2.0),
-        );
+        // Initial scale is half of final scale
+        let initial_scale = final_scale * 0.5;
 
-        let pan_distance = (ken_burns_end_pos.x.abs().max(ken_burns_end_pos.y.abs())).max(1.0);
-        let pan_factor   = pan_distance / image_size;
-        // Base scale is clamped between 0.8 and 1.0
-        let ken_burns_scale = (base_scale + pan_factor * 0.7).clamp(0.8,1.0);
+        let (ken_burns_scale, ken_burns_end_pos) = ken_burns_move(image_width, image_height, subject_rect, settings.focus, coverage);
 
         // println!("ken_burns_scale: {}", ken_burns_scale);
         // println!("ken_burns_end_pos: ({}, {})", ken_burns_end_pos.x, ken_burns_end_pos.y);
@@ -89,134 +119,177 @@ impl Slide {
         Self {
             image,
 
-            visible: false,
-            state: PushBoxState::Entering,
+            timing,
 
-            is_animating: false,
-            animation_timer: 0.0,
+            transition,
+            direction: options.direction,
+            caption: settings.caption.clone(),
 
             initial_scale,
             final_scale,
 
-            // Initial position is outside the left of the screen
-            position: Vector2::new(-0.5, 0.5),            
-            scale: initial_scale,
-
-            tween_entering:    ease::Tween::new(ease::cubic_out, -0.5, 0.5, ANIMATION_DURATION),
-            tween_zooming_in:  ease::Tween::new(ease::cubic_out, initial_scale, final_scale, ANIMATION_DURATION),
-            tween_zooming_out: ease::Tween::new(ease::cubic_out, final_scale, initial_scale, ANIMATION_DURATION),
-            tween_exiting:     ease::Tween::new(ease::cubic_out, 0.5, 1.5, ANIMATION_DURATION),
-            
-            // Ken Burns effect initialization
-            ken_burns_scale: 1.0,
-            ken_burns_pan: Vector2::new(0.0, 0.0),
-            
-            tween_ken_burns_scale: ease::Tween::new(ease::linear_none, 1.0, ken_burns_scale, DISPLAY_DURATION),
-            tween_ken_burns_pan_x: ease::Tween::new(ease::linear_none, 0.0, ken_burns_end_pos.x, DISPLAY_DURATION),
-            tween_ken_burns_pan_y: ease::Tween::new(ease::linear_none, 0.0, ken_burns_end_pos.y, DISPLAY_DURATION),           
+            // Progress along the direction of the push, the slide comes from outside the screen
+            track_entering:    Track::new(ease::cubic_out, -0.5, 0.5, config.timing.entering),
+            track_zooming_in:  Track::new(ease::cubic_out, initial_scale, final_scale, config.timing.zooming_in),
+            track_zooming_out: Track::new(ease::cubic_out, final_scale, initial_scale, config.timing.zooming_out),
+            track_exiting:     Track::new(ease::cubic_out, 0.5, 1.5, config.timing.exiting),
+
+            track_ken_burns_scale: Track::new(ease::linear_none, 1.0, ken_burns_scale, timing.displaying),
+            track_ken_burns_pan_x: Track::new(ease::linear_none, 0.0, ken_burns_end_pos.x, timing.displaying),
+            track_ken_burns_pan_y: Track::new(ease::linear_none, 0.0, ken_burns_end_pos.y, timing.displaying),
         }
     }
 
-    pub fn update(&mut self, dt: f32) {
-        if !self.is_animating {
+    /// Time from the slide entering the screen until it starts exiting, when the next slide enters
+    pub fn duration_until_exit(&self) -> f32 {
+        self.timing.entering + self.timing.zooming_in + self.timing.displaying + self.timing.zooming_out
+    }
+
+    pub fn exit_duration(&self) -> f32 {
+        self.timing.exiting
+    }
+
+    /// Phase of the slide `time` seconds after it started entering, and the time spent in it.
+    /// None before it enters and after it exited.
+    pub fn state_at(&self, time: f32) -> Option<(PushBoxState, f32)> {
+        phase_at(&[
+            (PushBoxState::Entering,   self.timing.entering),
+            (PushBoxState::ZoomingIn,  self.timing.zooming_in),
+            (PushBoxState::Displaying, self.timing.displaying),
+            (PushBoxState::ZoomingOut, self.timing.zooming_out),
+            (PushBoxState::Exiting,    self.timing.exiting),
+        ], time)
+    }
+
+    /// Draws the slide as it is `time` seconds after it started entering
+    pub fn draw(&self, d: &mut RaylibDrawHandle, config: &RenderConfig, time: f32) {
+        let Some((state, phase_time)) = self.state_at(time) else {
             return;
-        }
+        };
 
-        match self.state {
-            PushBoxState::Entering => {
-                self.scale = self.initial_scale;
-                self.position.x = self.tween_entering.apply(dt);
-            }
-            PushBoxState::ZoomingIn => {
-                self.position = Vector2::new(0.5, 0.5);
-                self.scale = self.tween_zooming_in.apply(dt);
-            }
-            PushBoxState::Displaying => {
-                self.position = Vector2::new(0.5, 0.5);
-                self.scale = self.final_scale;
-
-                // Animate Ken Burns effect
-                self.ken_burns_scale = self.tween_ken_burns_scale.apply(dt);
-                self.ken_burns_pan.x = self.tween_ken_burns_pan_x.apply(dt);
-                self.ken_burns_pan.y = self.tween_ken_burns_pan_y.apply(dt);
-            }
-            PushBoxState::ZoomingOut => {
-                self.position = Vector2::new(0.5, 0.5);
-                self.scale = self.tween_zooming_out.apply(dt);
-            }
-            PushBoxState::Exiting => {
-                self.scale = self.initial_scale;
-                self.position.x = self.tween_exiting.apply(dt);
-            }
-        }
+        // Position (relative to the screen), scale and opacity in the current phase
+        let center = Vector2::new(0.5, 0.5);
+        let (position, scale, alpha) = match state {
+            PushBoxState::Entering => match self.transition {
+                Transition::Push => (self.direction.position(self.track_entering.at(phase_time)), self.initial_scale, 1.0),
+                // Track goes from -0.5 to 0.5, use it as a 0.0 to 1.0 opacity
+                Transition::Fade => (center, self.initial_scale, self.track_entering.at(phase_time) + 0.5),
+                Transition::Cut => (center, self.final_scale, 1.0),
+            },
+            PushBoxState::ZoomingIn => match self.transition {
+                Transition::Cut => (center, self.final_scale, 1.0),
+                _ => (center, self.track_zooming_in.at(phase_time), 1.0),
+            },
+            PushBoxState::Displaying => (center, self.final_scale, 1.0),
+            PushBoxState::ZoomingOut => match self.transition {
+                Transition::Cut => (center, self.final_scale, 1.0),
+                _ => (center, self.track_zooming_out.at(phase_time), 1.0),
+            },
+            PushBoxState::Exiting => match self.transition {
+                Transition::Push => (self.direction.position(self.track_exiting.at(phase_time)), self.initial_scale, 1.0),
+                // Track goes from 0.5 to 1.5, use it as a 1.0 to 0.0 opacity
+                Transition::Fade => (center, self.initial_scale, 1.5 - self.track_exiting.at(phase_time)),
+                Transition::Cut => (center, self.final_scale, 0.0),
+            },
+        };
 
-        self.animation_timer += dt;
-        let expected_duration = match self.state {
-            PushBoxState::Displaying => DISPLAY_DURATION,
-            _ => ANIMATION_DURATION,
+        // Ken Burns effect, it stays at its end framing after the Displaying phase
+        let ken_burns_time = match state {
+            PushBoxState::Displaying => phase_time,
+            _ => self.timing.displaying,
         };
+        let ken_burns_scale = self.track_ken_burns_scale.at(ken_burns_time);
+        let ken_burns_pan = Vector2::new(
+            self.track_ken_burns_pan_x.at(ken_burns_time),
+            self.track_ken_burns_pan_y.at(ken_burns_time),
+        );
 
-        if self.animation_timer >= expected_duration {
-            self.animation_timer = 0.0;
-            match self.state {
-                PushBoxState::Entering   => self.state = PushBoxState::ZoomingIn,
-                PushBoxState::ZoomingIn  => self.state = PushBoxState::Displaying,
-                PushBoxState::Displaying => self.state = PushBoxState::ZoomingOut,
-                PushBoxState::ZoomingOut => self.state = PushBoxState::Exiting,
-                PushBoxState::Exiting    => { 
-                    self.is_animating = false; 
-                    self.visible = false; 
-                },
-            }
-        }        
-    }
+        let screen_width = config.width as f32;
+        let screen_height = config.height as f32;
 
-    pub fn draw(&self, d: &mut RaylibDrawHandle) {        
-        if self.visible {
-            let screen_width = RENDER_WIDTH as f32;
-            let screen_height = RENDER_HEIGHT as f32;
+        let tex_width = self.image.width() as f32;
+        let tex_height = self.image.height() as f32;
 
-            let tex_width = self.image.width() as f32;
-            let tex_height = self.image.height() as f32;
+        let scaled_width = tex_width * scale;
+        let scaled_height = tex_height * scale;
 
-            let scaled_width = tex_width * self.scale;
-            let scaled_height = tex_height * self.scale;
+        let draw_pos = Vector2::new(
+            screen_width * position.x - scaled_width * 0.5,
+            screen_height * position.y - scaled_height * 0.5,
+        );
 
-            let draw_pos = Vector2::new(
-                screen_width * self.position.x - scaled_width * 0.5,
-                screen_height * self.position.y - scaled_height * 0.5,
-            );
+        // Relative to the dest rectangle (ie. the center of the image)
+        let origin = Vector2::new(scaled_width * 0.5, scaled_height * 0.5);
 
-            // Relative to the dest rectangle (ie. the center of the image)
-            let origin = Vector2::new(scaled_width * 0.5, scaled_height * 0.5);
-
-            // Adjust source rectangle for Ken Burns effect during Displaying state
-            let source_rec = if self.state >= PushBoxState::Displaying {                                             
-                let scaled_ken_burns_width = tex_width * self.ken_burns_scale;
-                let scaled_ken_burns_height = tex_height * self.ken_burns_scale;
-
-                let pan_origin = Vector2::new(
-                    (tex_width - scaled_ken_burns_width) * 0.5,
-                    (tex_height - scaled_ken_burns_height) * 0.5
-                );
-
-                // Appliquer le panoramique avec des bornes pour rester dans l'image
-                let x = (pan_origin.x + self.ken_burns_pan.x).clamp(0.0, tex_width - scaled_ken_burns_width);
-                let y = (pan_origin.y + self.ken_burns_pan.y).clamp(0.0, tex_height - scaled_ken_burns_height);
-
-                Rectangle::new(x, y, scaled_ken_burns_width, scaled_ken_burns_height)
-            } else {
-                Rectangle::new(0.0, 0.0, tex_width, tex_height)
-            };
-
-            d.draw_texture_pro(
-                &self.image,
-                source_rec,
-                Rectangle::new(draw_pos.x + origin.x, draw_pos.y + origin.y, scaled_width, scaled_height),
-                origin,
-                0.0,
-                Color::WHITE,
+        // Adjust source rectangle for Ken Burns effect from the Displaying state
+        let source_rec = if state >= PushBoxState::Displaying {
+            let scaled_ken_burns_width = tex_width * ken_burns_scale;
+            let scaled_ken_burns_height = tex_height * ken_burns_scale;
+
+            let pan_origin = Vector2::new(
+                (tex_width - scaled_ken_burns_width) * 0.5,
+                (tex_height - scaled_ken_burns_height) * 0.5
             );
+
+            // Appliquer le panoramique avec des bornes pour rester dans l'image
+            let x = (pan_origin.x + ken_burns_pan.x).clamp(0.0, tex_width - scaled_ken_burns_width);
+            let y = (pan_origin.y + ken_burns_pan.y).clamp(0.0, tex_height - scaled_ken_burns_height);
+
+            Rectangle::new(x, y, scaled_ken_burns_width, scaled_ken_burns_height)
+        } else {
+            Rectangle::new(0.0, 0.0, tex_width, tex_height)
+        };
+
+        d.draw_texture_pro(
+            &self.image,
+            source_rec,
+            Rectangle::new(draw_pos.x + origin.x, draw_pos.y + origin.y, scaled_width, scaled_height),
+            origin,
+            0.0,
+            Color::new(255, 255, 255, (255.0 * alpha.clamp(0.0, 1.0)) as u8),
+        );
+
+        if state == PushBoxState::Displaying {
+            if let Some(caption) = &self.caption {
+                draw_caption(d, config, caption, 1.0);
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_SUBJECT: Rectangle = Rectangle { x: 0.0, y: 0.0, width: 0.0, height: 0.0 };
+
+    #[test]
+    fn the_pan_moves_towards_the_subject() {
+        // Subject in the top right quarter of a 4000x3000 photo
+        let subject = Rectangle::new(3000.0, 500.0, 500.0, 500.0);
+        let (_, end) = ken_burns_move(4000.0, 3000.0, subject, None, 1.0);
+        assert_eq!((end.x, end.y), (1250.0, -750.0));
+    }
+
+    #[test]
+    fn the_focus_point_wins_over_the_subjects() {
+        let subject = Rectangle::new(3000.0, 500.0, 500.0, 500.0);
+        let (_, end) = ken_burns_move(4000.0, 3000.0, subject, Some([0.25, 0.75]), 1.0);
+        assert_eq!((end.x, end.y), (-1000.0, 750.0));
+    }
+
+    #[test]
+    fn photos_without_subjects_zoom_on_their_center() {
+        let (scale, end) = ken_burns_move(3000.0, 4000.0, NO_SUBJECT, None, 1.0);
+        assert_eq!((end.x, end.y), (0.0, 0.0));
+        assert_eq!(scale, 1.0);
+    }
+
+    #[test]
+    fn the_zoom_stays_between_the_bounds() {
+        // A tiny centered subject zooms in as far as allowed: less for letterboxed photos
+        let subject = Rectangle::new(1950.0, 1450.0, 100.0, 100.0);
+        assert_eq!(ken_burns_move(4000.0, 3000.0, subject, None, 1.0).0, 0.8);
+        assert_eq!(ken_burns_move(4000.0, 3000.0, subject, None, 0.5).0, 0.85);
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum PushBoxState {
     Entering,
     ZoomingIn,
@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+use std::ffi::{c_char, c_void, CStr};
+use anyhow::{anyhow, bail, Result};
+use raylib::prelude::*;
+use crate::pipeline::FrameWriter;
+
+// OpenGL enums used for the pixel buffer objects
+const GL_FRAMEBUFFER: u32 = 0x8D40;
+const GL_PIXEL_PACK_BUFFER: u32 = 0x88EB;
+const GL_STREAM_READ: u32 = 0x88E1;
+const GL_READ_ONLY: u32 = 0x88B8;
+const GL_RGBA: u32 = 0x1908;
+const GL_UNSIGNED_BYTE: u32 = 0x1401;
+
+unsafe extern "C" {
+    // From the GLFW library built into raylib. raylib does not expose pixel buffer objects,
+    // their functions are loaded from the OpenGL context like raylib loads its own.
+    fn glfwGetProcAddress(name: *const c_char) -> *const c_void;
+}
+
+/// OpenGL functions needed to read frames back asynchronously
+pub struct Gl {
+    gen_buffers: unsafe extern "system" fn(n: i32, buffers: *mut u32),
+    delete_buffers: unsafe extern "system" fn(n: i32, buffers: *const u32),
+    bind_buffer: unsafe extern "system" fn(target: u32, buffer: u32),
+    buffer_data: unsafe extern "system" fn(target: u32, size: isize, data: *const c_void, usage: u32),
+    bind_framebuffer: unsafe extern "system" fn(target: u32, framebuffer: u32),
+    read_pixels: unsafe extern "system" fn(x: i32, y: i32, width: i32, height: i32, format: u32, kind: u32, pixels: *mut c_void),
+    map_buffer: unsafe extern "system" fn(target: u32, access: u32) -> *mut c_void,
+    unmap_buffer: unsafe extern "system" fn(target: u32) -> u8,
+}
+
+impl Gl {
+    /// Loads the functions from the current OpenGL context, None if one is missing
+    fn load() -> Option<Gl> {
+        /// # Safety
+        /// `F` must be the function pointer type of the OpenGL function `name`
+        unsafe fn function<F: Copy>(name: &CStr) -> Option<F> {
+            let address = unsafe { glfwGetProcAddress(name.as_ptr()) };
+            (!address.is_null()).then(|| unsafe { std::mem::transmute_copy(&address) })
+        }
+
+        unsafe {
+            Some(Gl {
+                gen_buffers: function(c"glGenBuffers")?,
+                delete_buffers: function(c"glDeleteBuffers")?,
+                bind_buffer: function(c"glBindBuffer")?,
+                buffer_data: function(c"glBufferData")?,
+                bind_framebuffer: function(c"glBindFramebuffer")?,
+                read_pixels: function(c"glReadPixels")?,
+                map_buffer: function(c"glMapBuffer")?,
+                unmap_buffer: function(c"glUnmapBuffer")?,
+            })
+        }
+    }
+}
+
+/// Reads rendered frames back from the GPU and hands them to a `FrameWriter`, as RGBA rows
+/// from bottom to top.
+pub enum Readback {
+    /// Every frame is copied to a new image, the render thread waits for the GPU each time
+    Sync,
+    /// The GPU copies every frame to one of a ring of pixel buffer objects while the next
+    /// frames are rendered. A frame is mapped and handed to the writer once the ring is full,
+    /// `buffers.len() - 1` frames later, when its copy is long done.
+    Async {
+        gl: Gl,
+        buffers: Vec<u32>,
+        pending: VecDeque<u32>, // buffers being filled, oldest first
+        width: i32,
+        height: i32,
+    },
+}
+
+impl Readback {
+    /// Asynchronous readback through `ring` pixel buffer objects, or synchronous readback when
+    /// the OpenGL context lacks them
+    pub fn new(width: i32, height: i32, ring: usize) -> Readback {
+        let Some(gl) = Gl::load() else {
+            eprintln!("Warning: pixel buffer objects are not available, frames are read back synchronously");
+            return Readback::Sync;
+        };
+
+        let size = width as isize * height as isize * 4;
+        let mut buffers = vec![0; ring.max(2)];
+        unsafe {
+            (gl.gen_buffers)(buffers.len() as i32, buffers.as_mut_ptr());
+            for &buffer in buffers.iter() {
+                (gl.bind_buffer)(GL_PIXEL_PACK_BUFFER, buffer);
+                (gl.buffer_data)(GL_PIXEL_PACK_BUFFER, size, std::ptr::null(), GL_STREAM_READ);
+            }
+            (gl.bind_buffer)(GL_PIXEL_PACK_BUFFER, 0);
+        }
+
+        Readback::Async { gl, buffers, pending: VecDeque::new(), width, height }
+    }
+
+    /// Reads `framebuffer` back. With asynchronous readback the frame only reaches the writer
+    /// a few frames later, or on `flush`.
+    pub fn read(&mut self, framebuffer: &RenderTexture2D, writer: &mut FrameWriter) -> Result<()> {
+        match self {
+            Readback::Sync => {
+                let image = framebuffer.load_image()
+                    .map_err(|e| anyhow!("Failed to load image from framebuffer: {}", e))?;
+                let width = image.width() as usize;
+                let pixels = unsafe {
+                    std::slice::from_raw_parts(image.data() as *const u8, width * image.height() as usize * 4)
+                };
+                writer.write(pixels, width)
+            }
+            Readback::Async { gl, buffers, pending, width, height } => {
+                if pending.len() == buffers.len() {
+                    Self::write_oldest(gl, pending, *width, *height, writer)?;
+                }
+                let buffer = buffers.iter().copied()
+                    .find(|buffer| !pending.contains(buffer))
+                    .expect("a buffer was just written");
+
+                // Returns at once, the GPU copies the pixels to the buffer when it gets there
+                unsafe {
+                    (gl.bind_framebuffer)(GL_FRAMEBUFFER, framebuffer.id);
+                    (gl.bind_buffer)(GL_PIXEL_PACK_BUFFER, buffer);
+                    (gl.read_pixels)(0, 0, *width, *height, GL_RGBA, GL_UNSIGNED_BYTE, std::ptr::null_mut());
+                    (gl.bind_buffer)(GL_PIXEL_PACK_BUFFER, 0);
+                    (gl.bind_framebuffer)(GL_FRAMEBUFFER, 0);
+                }
+                pending.push_back(buffer);
+                Ok(())
+            }
+        }
+    }
+
+    /// Hands the frames still being read back to the writer
+    pub fn flush(&mut self, writer: &mut FrameWriter) -> Result<()> {
+        if let Readback::Async { gl, pending, width, height, .. } = self {
+            while !pending.is_empty() {
+                Self::write_oldest(gl, pending, *width, *height, writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Maps the oldest pending buffer, waiting for its copy if needed, and writes its frame
+    fn write_oldest(gl: &Gl, pending: &mut VecDeque<u32>, width: i32, height: i32, writer: &mut FrameWriter) -> Result<()> {
+        let Some(buffer) = pending.pop_front() else {
+            return Ok(());
+        };
+        unsafe {
+            (gl.bind_buffer)(GL_PIXEL_PACK_BUFFER, buffer);
+            let data = (gl.map_buffer)(GL_PIXEL_PACK_BUFFER, GL_READ_ONLY);
+            if data.is_null() {
+                (gl.bind_buffer)(GL_PIXEL_PACK_BUFFER, 0);
+                bail!("Failed to map a frame read back from the GPU");
+            }
+            let pixels = std::slice::from_raw_parts(data as *const u8, width as usize * height as usize * 4);
+            let result = writer.write(pixels, width as usize);
+            (gl.unmap_buffer)(GL_PIXEL_PACK_BUFFER);
+            (gl.bind_buffer)(GL_PIXEL_PACK_BUFFER, 0);
+            result
+        }
+    }
+}
+
+impl Drop for Readback {
+    fn drop(&mut self) {
+        if let Readback::Async { gl, buffers, .. } = self {
+            unsafe { (gl.delete_buffers)(buffers.len() as i32, buffers.as_ptr()) };
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use walkdir::WalkDir;
+use crate::image_format::ImageFormat;
+
+/// Extensions of the files whose content is not recognized but left to raylib
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "bmp", "gif"];
+
+/// An image found in a source directory. The fields are in name order: path relative to the
+/// source, then source, then full path.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ScannedImage {
+    pub relative_path: PathBuf,
+    pub source: usize, // index of the source directory
+    pub path: PathBuf,
+}
+
+/// How images are found in the source directories
+#[derive(Clone)]
+pub struct ScanOptions {
+    pub recursive: bool,
+    pub follow_symlinks: bool, // otherwise symbolic links are skipped
+    pub include: Vec<String>,  // images must match one of these patterns when set
+    pub exclude: Vec<String>,
+    include_set: Option<GlobSet>,
+    exclude_set: GlobSet,
+}
+
+// The compiled patterns are left out: they are the patterns, and their debug output is huge
+impl fmt::Debug for ScanOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScanOptions")
+            .field("recursive", &self.recursive)
+            .field("follow_symlinks", &self.follow_symlinks)
+            .field("include", &self.include)
+            .field("exclude", &self.exclude)
+            .finish()
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(|e| format!("Invalid glob pattern '{}': {}", pattern, e))?);
+    }
+    builder.build().map_err(|e| format!("Invalid glob patterns: {}", e))
+}
+
+impl ScanOptions {
+    /// Patterns are matched against the path of the images relative to their source directory,
+    /// `*` matches `/` too: `*.jpg` selects the JPEG files of every subdirectory
+    pub fn new(recursive: bool, follow_symlinks: bool, include: &[String], exclude: &[String]) -> Result<Self, String> {
+        Ok(Self {
+            recursive,
+            follow_symlinks,
+            include: include.to_vec(),
+            exclude: exclude.to_vec(),
+            include_set: if include.is_empty() { None } else { Some(glob_set(include)?) },
+            exclude_set: glob_set(exclude)?,
+        })
+    }
+
+    fn selects(&self, relative_path: &Path) -> bool {
+        self.include_set.as_ref().is_none_or(|include| include.is_match(relative_path))
+            && !self.exclude_set.is_match(relative_path)
+    }
+}
+
+fn has_image_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+/// What a file of a source directory holds, from its first bytes
+#[derive(Debug, PartialEq)]
+enum FileKind {
+    Image,
+    Undisplayable(ImageFormat), // an image raylib cannot decode, such as a HEIC photo
+    Other,
+}
+
+fn file_kind(path: &Path) -> FileKind {
+    let mut head = Vec::with_capacity(16);
+    let _ = File::open(path).and_then(|file| file.take(16).read_to_end(&mut head));
+    match ImageFormat::detect(&head) {
+        Some(format) if format.is_decodable() => FileKind::Image,
+        Some(format) => FileKind::Undisplayable(format),
+        None if has_image_extension(path) => FileKind::Image,
+        None => FileKind::Other,
+    }
+}
+
+/// Images found in the source directories, and the images skipped as they cannot be displayed
+#[derive(Default)]
+struct Found {
+    images: Vec<ScannedImage>,
+    undisplayable: Vec<(PathBuf, ImageFormat)>,
+}
+
+/// Finds the images of source directory `index`
+fn scan_source(source: &Path, index: usize, options: &ScanOptions, found: &mut Found) -> Result<(), String> {
+    if !source.is_dir() {
+        return Err(format!("Failed to read directory {}: not a directory", source.display()));
+    }
+
+    let walker = WalkDir::new(source)
+        .min_depth(1)
+        .max_depth(if options.recursive { usize::MAX } else { 1 })
+        .follow_links(options.follow_symlinks);
+
+    for entry in walker {
+        // Unreadable subdirectories and symbolic link loops are skipped
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("Warning: {}", e);
+                continue;
+            }
+        };
+        if entry.path_is_symlink() && !options.follow_symlinks {
+            continue;
+        }
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative_path = entry.path().strip_prefix(source).unwrap_or(entry.path()).to_path_buf();
+        if !options.selects(&relative_path) {
+            continue;
+        }
+        match file_kind(entry.path()) {
+            FileKind::Image => found.images.push(ScannedImage { relative_path, source: index, path: entry.into_path() }),
+            FileKind::Undisplayable(format) => found.undisplayable.push((entry.into_path(), format)),
+            FileKind::Other => {}
+        }
+    }
+    Ok(())
+}
+
+/// Finds the images of every source directory. They are sorted by path relative to their
+/// source, so the images of nested `YYYY/MM/` folders from several drives are merged in order,
+/// then by source and full path for a deterministic order.
+///
+/// Files are recognized by their content, their extension only matters when it is not
+/// recognized. Images that cannot be displayed (WebP, HEIF, TIFF) are skipped with a warning.
+pub fn scan_images(sources: &[PathBuf], options: &ScanOptions) -> Result<Vec<ScannedImage>, String> {
+    let mut found = Found::default();
+    for (index, source) in sources.iter().enumerate() {
+        let count = found.images.len();
+        scan_source(source, index, options, &mut found)?;
+        if found.images.len() == count {
+            eprintln!("Warning: No images found in {}", source.display());
+        }
+    }
+    if let Some((first, _)) = found.undisplayable.first() {
+        let formats: BTreeSet<&str> = found.undisplayable.iter().map(|(_, format)| format.name()).collect();
+        eprintln!(
+            "Warning: Skipped {} image(s) that cannot be displayed ({}), such as {}",
+            found.undisplayable.len(),
+            formats.into_iter().collect::<Vec<_>>().join(", "),
+            first.display(),
+        );
+    }
+
+    let mut images = found.images;
+    if images.is_empty() {
+        return Err("No image files found".to_string());
+    }
+
+    images.sort();
+    Ok(images)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    const HEIC: &[u8] = b"\0\0\0\x18ftypheic\0\0\0\0";
+
+    #[test]
+    fn files_are_recognized_by_their_content() {
+        let dir = std::env::temp_dir().join(format!("slideshow-test-{}-scan", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files: &[(&str, &[u8])] = &[
+            ("screenshot.jpg", PNG),          // a PNG saved as .jpg
+            ("scan", PNG),                    // without extension
+            ("IMG_0001.HEIC", HEIC),
+            ("IMG_0002.jpg", HEIC),
+            ("truncated.JPG", b""),           // left to raylib, by extension
+            ("IMG_0001.jpg.toml", b"hero = true\n"),
+        ];
+        for (name, bytes) in files {
+            std::fs::write(dir.join(name), bytes).unwrap();
+        }
+
+        let kinds: Vec<FileKind> = files.iter().map(|(name, _)| file_kind(&dir.join(name))).collect();
+        assert_eq!(kinds, vec![
+            FileKind::Image,
+            FileKind::Image,
+            FileKind::Undisplayable(ImageFormat::Heif),
+            FileKind::Undisplayable(ImageFormat::Heif),
+            FileKind::Image,
+            FileKind::Other,
+        ]);
+
+        let options = ScanOptions::new(false, false, &[], &[]).unwrap();
+        let images = scan_images(std::slice::from_ref(&dir), &options).unwrap();
+        let names: Vec<&Path> = images.iter().map(|image| image.relative_path.as_path()).collect();
+        assert_eq!(names, vec![Path::new("scan"), Path::new("screenshot.jpg"), Path::new("truncated.JPG")]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,184 @@
+use std::cell::RefCell;
+use std::path::PathBuf;
+use raylib::prelude::*;
+use crate::config::RenderConfig;
+use crate::constants::DEFAULT_SEGMENT_TRANSITION;
+use crate::engine::{find_engine, resolve_options, Engine, EngineInfo, EngineOptions};
+use crate::slide_settings::SlideSource;
+use crate::sequence::options::{Segment, SegmentTransition, SequenceOptions};
+use crate::sequence::transition::{black_at, crossfade_at, part_at, Span};
+
+/// A segment of the sequence, with its engine
+struct Part {
+    name: &'static str,
+    engine: Box<dyn Engine>,
+    first_slide: usize, // index of its first slide in the sequence
+}
+
+/// Runs an engine per segment. Every segment engine is initialized on its own photos, as if
+/// it were the only engine: it loads their textures, and push-box its subject detection
+/// model, again. A photo shown by two segments is loaded twice, memory use grows with the
+/// number of segments.
+pub struct SequenceEngine {
+    config: RenderConfig,
+    options: EngineOptions, // segments create their engines with the same options
+    parts: Vec<Part>,
+    spans: Vec<Span>, // where each part sits on the timeline
+    scratch: RefCell<Option<RenderTexture2D>>, // the part fading in during a crossfade
+}
+
+/// Photos of `sources` selected by `segment`, matched by canonical path (`canonical`, in the
+/// same order as `sources`) like the paths of the project file
+fn select(sources: &[SlideSource], canonical: &[PathBuf], segment: &Segment) -> Vec<SlideSource> {
+    let selected: Vec<(&SlideSource, &PathBuf)> = if segment.photos.is_empty() {
+        sources.iter().zip(canonical).collect()
+    } else {
+        segment.photos.iter()
+            .filter_map(|photo| {
+                let source = sources.iter().zip(canonical).find(|(_, path)| *path == photo);
+                if source.is_none() {
+                    eprintln!("Warning: sequence: {} is not one of the photos of the slideshow", photo.display());
+                }
+                source
+            })
+            .collect()
+    };
+
+    selected.into_iter()
+        .filter(|(_, path)| segment.directory.as_ref().is_none_or(|directory| path.starts_with(directory)))
+        .filter(|(source, _)| !segment.heroes || source.settings.hero)
+        .map(|(source, _)| source.clone())
+        .collect()
+}
+
+impl SequenceEngine {
+    pub const INFO: EngineInfo = EngineInfo {
+        name: "sequence",
+        description: "Runs other engines one after the other on subsets of the photos, as set by [[engines.sequence.segments]] in the project file",
+        options: <SequenceOptions as clap::Args>::augment_args,
+        resolve_options: resolve_options::<SequenceOptions>,
+        // Its segments run the other engines with their options
+        uses: |options| options.get::<SequenceOptions>().segments.iter()
+            .filter_map(|segment| find_engine(&segment.engine))
+            .map(|engine| engine.name)
+            .collect(),
+        create: |config, options| Box::new(SequenceEngine::new(config, options.clone())),
+    };
+
+    pub fn new(config: RenderConfig, options: EngineOptions) -> Self {
+        Self {
+            config,
+            options,
+            parts: Vec::new(),
+            spans: Vec::new(),
+            scratch: RefCell::new(None),
+        }
+    }
+
+}
+
+impl Engine for SequenceEngine {
+    fn initialize(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, sources: Vec<SlideSource>) -> bool {
+        let segments = self.options.get::<SequenceOptions>().segments.clone();
+        let canonical: Vec<PathBuf> = sources.iter()
+            .map(|source| source.path.canonicalize().unwrap_or(source.path.clone()))
+            .collect();
+        let mut first_slide = 0;
+
+        for (i, segment) in segments.iter().enumerate() {
+            let info = find_engine(&segment.engine).expect("segment engines are checked with the settings");
+            let selected = select(&sources, &canonical, segment);
+            eprintln!("Sequence segment {}: {} with {} photo(s)", i + 1, info.name, selected.len());
+
+            let mut engine = (info.create)(self.config.clone(), &self.options);
+            if !engine.initialize(rl, thread, selected) {
+                eprintln!("Sequence segment {} ({}): no slides were created", i + 1, info.name);
+                return false;
+            }
+            let transition_duration = segment.transition_duration.unwrap_or(DEFAULT_SEGMENT_TRANSITION);
+            let span = Span::after(self.spans.last(), engine.total_duration(), segment.transition, transition_duration);
+
+            let slide_count = engine.slide_count();
+            self.parts.push(Part { name: info.name, engine, first_slide });
+            self.spans.push(span);
+            first_slide += slide_count;
+        }
+
+        if self.spans.iter().any(|span| span.transition == SegmentTransition::Crossfade) {
+            match rl.load_render_texture(thread, self.config.width as u32, self.config.height as u32) {
+                Ok(scratch) => self.scratch = RefCell::new(Some(scratch)),
+                Err(e) => {
+                    eprintln!("Failed to create crossfade frame buffer: {}", e);
+                    return false;
+                }
+            }
+        }
+
+        !self.parts.is_empty()
+    }
+
+    fn total_duration(&self) -> f32 {
+        self.spans.last().map_or(0.0, |span| span.end())
+    }
+
+    fn slide_count(&self) -> usize {
+        self.parts.iter().map(|part| part.engine.slide_count()).sum()
+    }
+
+    fn slide_start(&self, index: usize) -> f32 {
+        self.parts.iter().zip(self.spans.iter())
+            .rfind(|(part, _)| part.first_slide <= index)
+            .map_or(0.0, |(part, span)| span.start + part.engine.slide_start(index - part.first_slide))
+    }
+
+    fn slide_at(&self, time: f32) -> (usize, String) {
+        let index = part_at(&self.spans, time);
+        let part = &self.parts[index];
+        let (index, state) = part.engine.slide_at(time - self.spans[index].start);
+        (part.first_slide + index, format!("{} {}", part.name, state))
+    }
+
+    fn render_at(&self, time: f32, rl: &mut RaylibHandle, thread: &RaylibThread, framebuffer: &mut RenderTexture2D) {
+        let index = part_at(&self.spans, time);
+
+        // During a crossfade the previous part is still playing under this one
+        let alpha = crossfade_at(&self.spans, index, time);
+        let base_index = if alpha.is_some() { index - 1 } else { index };
+        self.parts[base_index].engine.render_at(time - self.spans[base_index].start, rl, thread, framebuffer);
+
+        let black = black_at(&self.spans, base_index, time);
+        if alpha.is_none() && black <= 0.0 {
+            return;
+        }
+
+        let mut scratch = self.scratch.borrow_mut();
+        let crossfade = match (scratch.as_mut(), alpha) {
+            (Some(scratch), Some(alpha)) => {
+                self.parts[index].engine.render_at(time - self.spans[index].start, rl, thread, scratch);
+                Some((scratch, alpha))
+            }
+            _ => None,
+        };
+
+        let width = self.config.width as f32;
+        let height = self.config.height as f32;
+        rl.draw_texture_mode(thread, framebuffer, |mut tmd| {
+            let mut d = tmd.begin_drawing(thread);
+
+            if let Some((scratch, alpha)) = crossfade {
+                d.draw_texture_pro(
+                    &*scratch,
+                    // Render textures are stored upside down
+                    Rectangle::new(0.0, 0.0, width, -height),
+                    Rectangle::new(0.0, 0.0, width, height),
+                    Vector2::new(0.0, 0.0),
+                    0.0,
+                    Color::new(255, 255, 255, (255.0 * alpha) as u8),
+                );
+            }
+            if black > 0.0 {
+                d.draw_rectangle(0, 0, self.config.width, self.config.height, Color::new(0, 0, 0, (255.0 * black) as u8));
+            }
+        });
+    }
+}
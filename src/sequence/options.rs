@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use crate::engine::{find_engine, EngineOptionSet};
+
+/// How a segment follows the previous one
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SegmentTransition {
+    #[default]
+    Cut,       // starts when the previous segment ends
+    Fade,      // the previous segment fades to black, then this one fades in
+    Crossfade, // starts before the previous segment ends and fades in over it
+}
+
+/// A part of the sequence: an engine rendering a subset of the photos. Without any filter the
+/// segment shows every photo.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Segment {
+    pub engine: String,
+    /// Only the photos under this directory
+    pub directory: Option<PathBuf>,
+    /// Only these photos, in this order
+    #[serde(default)]
+    pub photos: Vec<PathBuf>,
+    /// Only the hero slides
+    #[serde(default)]
+    pub heroes: bool,
+    #[serde(default)]
+    pub transition: SegmentTransition,
+    pub transition_duration: Option<f32>, // seconds
+}
+
+/// Options of the sequence engine, only set in the project file:
+///
+/// ```toml
+/// engine = "sequence"
+///
+/// [[engines.sequence.segments]]
+/// engine = "spiral"
+/// directory = "Family/Intro"
+///
+/// [[engines.sequence.segments]]
+/// engine = "push-box"
+/// heroes = true
+/// transition = "crossfade"
+/// ```
+///
+/// Paths are resolved against the directory of the project file.
+#[derive(Debug, Clone, Default, Deserialize, clap::Args)]
+#[serde(deny_unknown_fields)]
+pub struct SequenceOptions {
+    #[arg(skip)]
+    #[serde(default)]
+    pub segments: Vec<Segment>,
+}
+
+impl EngineOptionSet for SequenceOptions {
+    type Overrides = SequenceOptions;
+
+    fn apply(&mut self, overrides: &SequenceOptions) {
+        if !overrides.segments.is_empty() {
+            self.segments = overrides.segments.clone();
+        }
+    }
+
+    // Photos of sequence segments are matched against scanned images by canonical path
+    fn resolve_paths(overrides: &mut SequenceOptions, base_dir: &Path) -> Result<(), String> {
+        for segment in overrides.segments.iter_mut() {
+            if let Some(directory) = segment.directory.as_mut() {
+                *directory = base_dir.join(&directory).canonicalize()
+                    .map_err(|e| format!("engines.sequence: '{}': {}", directory.display(), e))?;
+            }
+            for photo in segment.photos.iter_mut() {
+                *photo = base_dir.join(&photo).canonicalize()
+                    .map_err(|e| format!("engines.sequence: '{}': {}", photo.display(), e))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, segment) in self.segments.iter().enumerate() {
+            if segment.engine == "sequence" || find_engine(&segment.engine).is_none() {
+                return Err(format!("Sequence segment {}: unknown engine '{}' (a segment cannot be a sequence)", i + 1, segment.engine));
+            }
+            if let Some(duration) = segment.transition_duration.filter(|duration| !duration.is_finite() || *duration <= 0.0) {
+                return Err(format!("Sequence segment {}: transition duration {} is not a positive number of seconds", i + 1, duration));
+            }
+        }
+        Ok(())
+    }
+}
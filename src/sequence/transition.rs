@@ -0,0 +1,145 @@
+use crate::sequence::options::SegmentTransition;
+
+/// Where a part of the sequence sits on its timeline, and how it follows the previous part
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: f32, // time the part starts in the sequence
+    pub duration: f32,
+    pub transition: SegmentTransition,
+    pub transition_duration: f32,
+}
+
+impl Span {
+    /// Places a part lasting `duration` after `previous`. The first part has nothing to follow.
+    /// Transitions never last longer than the parts they join.
+    pub fn after(previous: Option<&Span>, duration: f32, transition: SegmentTransition, transition_duration: f32) -> Span {
+        let Some(previous) = previous else {
+            return Span { start: 0.0, duration, transition: SegmentTransition::Cut, transition_duration: 0.0 };
+        };
+        let transition_duration = transition_duration.min(previous.duration).min(duration);
+        let transition = if transition_duration > 0.0 { transition } else { SegmentTransition::Cut };
+        let start = match transition {
+            SegmentTransition::Crossfade => previous.end() - transition_duration,
+            _ => previous.end(),
+        };
+        Span { start, duration, transition, transition_duration }
+    }
+
+    pub fn end(&self) -> f32 {
+        self.start + self.duration
+    }
+}
+
+/// Index of the last part started at `time`
+pub fn part_at(spans: &[Span], time: f32) -> usize {
+    spans.iter().rposition(|span| span.start <= time).unwrap_or(0)
+}
+
+/// Opacity (0.0 - 1.0) of the black covering part `index` at `time`, when it fades in or the
+/// next part fades in after it
+pub fn black_at(spans: &[Span], index: usize, time: f32) -> f32 {
+    let span = &spans[index];
+    let mut black: f32 = 0.0;
+    if span.transition == SegmentTransition::Fade {
+        black = black.max(1.0 - (time - span.start) / span.transition_duration);
+    }
+    if let Some(next) = spans.get(index + 1).filter(|next| next.transition == SegmentTransition::Fade) {
+        black = black.max(1.0 - (span.end() - time) / next.transition_duration);
+    }
+    black.clamp(0.0, 1.0)
+}
+
+/// Opacity (0.0 - 1.0) of part `index` drawn over the previous one at `time`, None unless it
+/// is crossfading in
+pub fn crossfade_at(spans: &[Span], index: usize, time: f32) -> Option<f32> {
+    let span = &spans[index];
+    let crossfading = index > 0
+        && span.transition == SegmentTransition::Crossfade
+        && time < span.start + span.transition_duration;
+    crossfading.then(|| ((time - span.start) / span.transition_duration).clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use SegmentTransition::*;
+
+    /// Parts of 10 seconds following each other with `transitions` of 2 seconds
+    fn laid_out(transitions: &[SegmentTransition]) -> Vec<Span> {
+        let mut spans: Vec<Span> = Vec::new();
+        for transition in transitions {
+            spans.push(Span::after(spans.last(), 10.0, *transition, 2.0));
+        }
+        spans
+    }
+
+    #[test]
+    fn parts_are_laid_out_end_to_end_or_overlapping_for_crossfades() {
+        let spans = laid_out(&[Crossfade, Cut, Fade, Crossfade]);
+        let starts: Vec<f32> = spans.iter().map(|span| span.start).collect();
+        assert_eq!(starts, vec![0.0, 10.0, 20.0, 28.0]);
+        assert_eq!(spans[0].transition, Cut, "the first part follows nothing");
+        assert_eq!(spans[3].end(), 38.0);
+    }
+
+    #[test]
+    fn transitions_are_shortened_to_the_parts_they_join() {
+        let first = Span::after(None, 1.0, Cut, 0.0);
+        let second = Span::after(Some(&first), 5.0, Crossfade, 2.0);
+        assert_eq!(second.transition_duration, 1.0);
+        assert_eq!(second.start, 0.0);
+
+        let empty = Span::after(Some(&second), 0.0, Fade, 2.0);
+        assert_eq!(empty.transition, Cut);
+        assert_eq!(empty.start, 5.0);
+    }
+
+    #[test]
+    fn part_at_finds_the_last_started() {
+        let spans = laid_out(&[Cut, Crossfade, Cut]);
+        assert_eq!(part_at(&spans, 0.0), 0);
+        assert_eq!(part_at(&spans, 7.9), 0);
+        assert_eq!(part_at(&spans, 8.0), 1);
+        assert_eq!(part_at(&spans, 18.0), 2);
+        assert_eq!(part_at(&spans, 100.0), 2);
+        assert_eq!(part_at(&spans, -1.0), 0);
+    }
+
+    #[test]
+    fn fades_go_through_black() {
+        let spans = laid_out(&[Cut, Fade]);
+        // The first part fades out over the last 2 seconds...
+        assert_eq!(black_at(&spans, 0, 7.0), 0.0);
+        assert_eq!(black_at(&spans, 0, 8.0), 0.0);
+        assert_eq!(black_at(&spans, 0, 9.0), 0.5);
+        assert_eq!(black_at(&spans, 0, 10.0), 1.0);
+        // ...then the second one fades in over its first 2 seconds
+        assert_eq!(black_at(&spans, 1, 10.0), 1.0);
+        assert_eq!(black_at(&spans, 1, 11.0), 0.5);
+        assert_eq!(black_at(&spans, 1, 12.0), 0.0);
+        assert_eq!(black_at(&spans, 1, 15.0), 0.0);
+    }
+
+    #[test]
+    fn cuts_and_crossfades_have_no_black() {
+        let spans = laid_out(&[Cut, Cut, Crossfade]);
+        for time in [0.0, 9.0, 10.0, 17.0, 18.0, 19.0, 28.0] {
+            for index in 0..spans.len() {
+                assert_eq!(black_at(&spans, index, time), 0.0, "part {} at {}", index, time);
+            }
+        }
+    }
+
+    #[test]
+    fn crossfades_blend_over_the_previous_part() {
+        let spans = laid_out(&[Cut, Crossfade]);
+        assert_eq!(crossfade_at(&spans, 0, 9.0), None);
+        assert_eq!(crossfade_at(&spans, 1, 8.0), Some(0.0));
+        assert_eq!(crossfade_at(&spans, 1, 9.0), Some(0.5));
+        assert_eq!(crossfade_at(&spans, 1, 10.0), None);
+        assert_eq!(crossfade_at(&spans, 1, 15.0), None);
+
+        let spans = laid_out(&[Cut, Fade]);
+        assert_eq!(crossfade_at(&spans, 1, 10.5), None);
+    }
+}
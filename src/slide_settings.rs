@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use crate::clock::{capture_date, ClockOffset};
+use crate::constants::*;
+
+/// How a slide comes in and goes out
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transition {
+    Push, // default: slide moves in / out of the screen
+    Fade, // slide fades in / out in place
+    Cut,  // slide appears / disappears instantly
+}
+
+/// Per-slide overrides, read from the `[slides."<path>"]` tables of the project file or
+/// from a `<image>.toml` sidecar file next to the image, e.g. `IMG_0001.jpg.toml`:
+///
+/// ```toml
+/// hero = true              # stays on screen twice as long
+/// duration = 4.0           # display duration (seconds), wins over `hero`
+/// focus = [0.3, 0.4]       # Ken Burns target, relative to the image (0.0 - 1.0)
+/// caption = "Party {date}" # {date}: capture date (YYYY-MM-DD) with clock offsets
+/// transition = "fade"      # push, fade or cut
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SlideSettings {
+    pub duration: Option<f32>,
+    #[serde(default)]
+    pub hero: bool,
+    pub focus: Option<[f32; 2]>,
+    pub caption: Option<String>,
+    pub transition: Option<Transition>,
+}
+
+impl SlideSettings {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read slide settings {}: {}", path.display(), e))?;
+        let settings: SlideSettings = toml::from_str(&content)
+            .map_err(|e| format!("Invalid slide settings {}: {}", path.display(), e))?;
+        settings.validate()
+            .map_err(|e| format!("Invalid slide settings {}: {}", path.display(), e))?;
+        Ok(settings)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(duration) = self.duration {
+            if !duration.is_finite() || duration <= 0.0 {
+                return Err(format!("duration: {} is not a positive number of seconds", duration));
+            }
+        }
+        if let Some([x, y]) = self.focus {
+            if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) {
+                return Err(format!("focus: [{}, {}] must be relative coordinates between 0.0 and 1.0", x, y));
+            }
+        }
+        Ok(())
+    }
+
+    /// Values set in `other` replace the values of this slide
+    pub fn merge(&mut self, other: &SlideSettings) {
+        if other.duration.is_some() {
+            self.duration = other.duration;
+        }
+        self.hero = self.hero || other.hero;
+        if other.focus.is_some() {
+            self.focus = other.focus;
+        }
+        if other.caption.is_some() {
+            self.caption = other.caption.clone();
+        }
+        if other.transition.is_some() {
+            self.transition = other.transition;
+        }
+    }
+
+    /// How long the slide is displayed, given the engine's default duration
+    pub fn display_duration(&self, default: f32) -> f32 {
+        match self.duration {
+            Some(duration) => duration,
+            None if self.hero => default * HERO_DURATION_FACTOR,
+            None => default,
+        }
+    }
+
+    pub fn transition(&self) -> Transition {
+        self.transition.unwrap_or(Transition::Push)
+    }
+}
+
+/// An image to render along with its settings
+#[derive(Debug, Clone)]
+pub struct SlideSource {
+    pub path: PathBuf,
+    pub settings: SlideSettings,
+}
+
+fn sidecar_path(image_path: &Path) -> PathBuf {
+    let mut name = image_path.as_os_str().to_os_string();
+    name.push(".toml");
+    PathBuf::from(name)
+}
+
+/// Replaces `{date}` in a caption with the capture date of the photo, corrected by the clock
+/// offsets. Photos without EXIF date get no date.
+fn date_caption(caption: &str, path: &Path, offsets: &[ClockOffset]) -> String {
+    if !caption.contains("{date}") {
+        return caption.to_string();
+    }
+    let date = capture_date(path, offsets).unwrap_or_else(|| {
+        eprintln!("Warning: No capture date for the caption of {}", path.display());
+        String::new()
+    });
+    caption.replace("{date}", &date)
+}
+
+/// Attaches settings to every image: sidecar file first, then the project file overrides.
+/// `overrides` keys must be canonical paths.
+pub fn load_slide_sources(
+    paths: Vec<PathBuf>,
+    overrides: &BTreeMap<PathBuf, SlideSettings>,
+    offsets: &[ClockOffset],
+) -> Result<Vec<SlideSource>, String> {
+    let mut sources = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        let mut settings = SlideSettings::default();
+
+        let sidecar = sidecar_path(&path);
+        if sidecar.is_file() {
+            settings.merge(&SlideSettings::load(&sidecar)?);
+        }
+
+        if let Some(project_settings) = path.canonicalize().ok().and_then(|p| overrides.get(&p)) {
+            settings.merge(project_settings);
+        }
+        settings.caption = settings.caption.map(|caption| date_caption(&caption, &path, offsets));
+
+        sources.push(SlideSource { path, settings });
+    }
+
+    Ok(sources)
+}
@@ -0,0 +1,177 @@
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::str::Chars;
+use std::time::UNIX_EPOCH;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+use crate::clock::{capture_time, file_time_offset, ClockOffset};
+use crate::scan::ScannedImage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SortKey {
+    #[default]
+    Name,     // path relative to the image directory
+    Natural,  // same, numbers compared by value: IMG_2 before IMG_10
+    Mtime,    // file modification time
+    ExifDate, // capture date, modification time without EXIF date
+    Random,   // shuffled with the seed of the render
+}
+
+/// Order of the photos, `--sort exif-date,reverse`
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub struct SortOrder {
+    pub key: SortKey,
+    pub reverse: bool,
+}
+
+impl TryFrom<String> for SortOrder {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        parse_sort_order(&text)
+    }
+}
+
+/// Parses `KEY`, `KEY,reverse` or `reverse` (reversed name order)
+pub fn parse_sort_order(text: &str) -> Result<SortOrder, String> {
+    let invalid = || format!(
+        "Invalid sort order '{}' (expected one of name, natural, mtime, exif-date or random, optionally followed by ',reverse')",
+        text
+    );
+    let parts: Vec<&str> = text.split(',').map(str::trim).collect();
+    let (key, reverse) = match parts.as_slice() {
+        ["reverse"] => ("name", true),
+        [key] => (*key, false),
+        [key, "reverse"] => (*key, true),
+        _ => return Err(invalid()),
+    };
+    let key = match key {
+        "name" => SortKey::Name,
+        "natural" => SortKey::Natural,
+        "mtime" => SortKey::Mtime,
+        "exif-date" => SortKey::ExifDate,
+        "random" => SortKey::Random,
+        _ => return Err(invalid()),
+    };
+    Ok(SortOrder { key, reverse })
+}
+
+/// Digits at the start of `chars`, without leading zeros
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits.trim_start_matches('0').to_string()
+}
+
+/// Compares numbers in the strings by value and the rest character by character
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                // Without leading zeros, a longer number is a bigger one
+                let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn modification_time(path: &Path) -> i64 {
+    let Ok(modified) = std::fs::metadata(path).and_then(|metadata| metadata.modified()) else {
+        return 0;
+    };
+    match modified.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    }
+}
+
+/// Sorts the scanned images (in name order) and returns their paths. Ties keep the name order.
+/// Capture dates are corrected by the clock offsets, and so are the modification times used
+/// instead by the offsets of their directory.
+pub fn sort_images(mut images: Vec<ScannedImage>, order: SortOrder, seed: u64, offsets: &[ClockOffset]) -> Vec<PathBuf> {
+    match order.key {
+        SortKey::Name => {}
+        SortKey::Natural => images.sort_by(|a, b| {
+            natural_cmp(&a.relative_path.to_string_lossy(), &b.relative_path.to_string_lossy()).then_with(|| a.cmp(b))
+        }),
+        SortKey::Mtime => images.sort_by_cached_key(|image| (modification_time(&image.path), image.clone())),
+        SortKey::ExifDate => images.sort_by_cached_key(|image| {
+            let time = capture_time(&image.path, offsets)
+                .unwrap_or_else(|| modification_time(&image.path) + file_time_offset(&image.path, offsets));
+            (time, image.clone())
+        }),
+        SortKey::Random => images.shuffle(&mut StdRng::seed_from_u64(seed)),
+    }
+    if order.reverse {
+        images.reverse();
+    }
+    images.into_iter().map(|image| image.path).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_are_compared_by_value() {
+        assert_eq!(natural_cmp("IMG_2.jpg", "IMG_10.jpg"), Ordering::Less);
+        assert_eq!(natural_cmp("IMG_10.jpg", "IMG_9.jpg"), Ordering::Greater);
+        assert_eq!(natural_cmp("2019/IMG_1", "2020/IMG_0"), Ordering::Less);
+        assert_eq!(natural_cmp("a1b2", "a1b10"), Ordering::Less);
+    }
+
+    #[test]
+    fn leading_zeros_do_not_change_the_value() {
+        assert_eq!(natural_cmp("IMG_007", "IMG_7"), Ordering::Equal);
+        assert_eq!(natural_cmp("IMG_007", "IMG_10"), Ordering::Less);
+        assert_eq!(natural_cmp("IMG_000", "IMG_0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn text_is_compared_character_by_character() {
+        assert_eq!(natural_cmp("a", "b"), Ordering::Less);
+        assert_eq!(natural_cmp("IMG", "IMG_1"), Ordering::Less);
+        assert_eq!(natural_cmp("", ""), Ordering::Equal);
+        assert_eq!(natural_cmp("IMG_1a", "IMG_1"), Ordering::Greater);
+    }
+
+    #[test]
+    fn sort_orders_are_parsed() {
+        assert_eq!(parse_sort_order("name"), Ok(SortOrder { key: SortKey::Name, reverse: false }));
+        assert_eq!(parse_sort_order("exif-date,reverse"), Ok(SortOrder { key: SortKey::ExifDate, reverse: true }));
+        assert_eq!(parse_sort_order("natural, reverse"), Ok(SortOrder { key: SortKey::Natural, reverse: true }));
+        assert_eq!(parse_sort_order("reverse"), Ok(SortOrder { key: SortKey::Name, reverse: true }));
+        assert!(parse_sort_order("size").is_err());
+        assert!(parse_sort_order("mtime,backwards").is_err());
+        assert!(parse_sort_order("").is_err());
+    }
+
+    #[test]
+    fn only_one_sort_key_is_accepted() {
+        for text in ["name,natural", "mtime,exif-date,reverse", "reverse,name", "name,reverse,reverse", "reverse,reverse"] {
+            assert!(parse_sort_order(text).is_err(), "{}", text);
+        }
+    }
+}
@@ -1,144 +1,116 @@
 use crate::spiral::layout::Layout;
 use crate::spiral::state::SpiralState;
 use raylib::prelude::*;
-use std::path::PathBuf;
 use crate::texture_loader::load_texture_with_exif_rotation;
-use crate::constants::*;
+use crate::config::RenderConfig;
+use crate::slide_settings::SlideSource;
+use crate::caption::draw_caption;
+use crate::timeline::{index_at, start_times};
+use crate::engine::{resolve_options, Engine, EngineInfo};
+use crate::spiral::options::{SpiralOptions, SpiralOverrides};
 
 pub struct SpiralEngine {
+    config: RenderConfig,
     layout: Layout,
 
-    state: SpiralState,
-
-    current_slide_index: usize,
-    display_timer: f32,
-    cleanup_timer: f32,
-    cleanup_index: usize,
+    starts: Vec<f32>,   // time each slide shows up at the center of the screen
+    cleanup_start: f32, // time the last slide is on the wall and the wall starts to be cleaned up
 }
 
-impl crate::engine::Engine for SpiralEngine {
-    fn new() -> Self {
+impl SpiralEngine {
+    pub const INFO: EngineInfo = EngineInfo {
+        name: "spiral",
+        description: "Each photo is shown at the center, then joins a photo wall filled in a spiral",
+        options: <SpiralOverrides as clap::Args>::augment_args,
+        resolve_options: resolve_options::<SpiralOptions>,
+        uses: |_| Vec::new(),
+        create: |config, options| Box::new(SpiralEngine::new(config, *options.get::<SpiralOptions>())),
+    };
+
+    pub fn new(config: RenderConfig, options: SpiralOptions) -> Self {
         Self {
-            layout: Layout::new(),
-            state: SpiralState::Displaying,
-            current_slide_index: 0,
-            display_timer: 0.0,
-            cleanup_timer: 0.0,
-            cleanup_index: 0,
+            layout: Layout::new(config.clone(), options),
+            config,
+            starts: Vec::new(),
+            cleanup_start: 0.0,
         }
     }
+}
 
-    fn initialize(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, paths: Vec<PathBuf>) -> bool {
-        for path in paths {
+impl Engine for SpiralEngine {
+    fn initialize(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, sources: Vec<SlideSource>) -> bool {
+        for SlideSource { path, settings } in sources {
             match load_texture_with_exif_rotation(rl, thread, &path) {
                 Ok(image) => {
-                    self.layout.add_image(image);
+                    self.layout.add_image(image, &settings);
                 }
                 Err(e) => {
-                    println!("Failed to load image: {}", e);
+                    eprintln!("Failed to load image: {}", e);
                 }
             }
         }
         self.layout.compute_layout();
 
-        // Set cleanup index to the last slide
-        self.cleanup_index = if self.layout.slides.len() > 0 { self.layout.slides.len() - 1 } else { 0 };
+        // Every slide is displayed then moves to the wall, then the next one shows up
+        let durations = self.layout.slides.iter()
+            .map(|slide| slide.display_duration + slide.transition_duration());
+        self.starts = start_times(durations.clone());
+        self.cleanup_start = durations.sum();
 
-        return !self.layout.slides.is_empty();
+        !self.layout.slides.is_empty()
     }
 
-    fn render_frame(&mut self, dt: f32, rl: &mut RaylibHandle, thread: &RaylibThread, framebuffer: &mut RenderTexture2D) -> bool {
-        for slide in self.layout.slides.iter_mut() {
-            slide.update(dt);
-        }
+    fn total_duration(&self) -> f32 {
+        // The wall is cleaned up one slide at a time
+        self.cleanup_start + self.layout.slides.len() as f32 * self.config.timing.spiral_cleanup
+    }
 
-        // 2. Update slideshow state machine
-        match self.state {
-            SpiralState::Displaying => {
-                self.display_timer += dt;
-                if self.display_timer >= DISPLAY_DURATION {
-                    // Time to start transition
-                    if self.current_slide_index < self.layout.slides.len() {
-                        // Start the current slide's background animation
-                        self.layout.slides[self.current_slide_index].start_background_animation();
-                        // Change state to wait for animation to finish
-                        self.state = SpiralState::Transitioning;
-                        // DO NOT increment index or reset timer here yet
-                    } else {
-                        // Should not happen if logic is correct, but safety first
-                        self.state = SpiralState::Cleanup;
-                        self.cleanup_timer = 0.0;
-                    }
-                }
-            }
-            SpiralState::Transitioning => {
-                // Check if the *current* slide (which is animating) has finished
-                if self.current_slide_index < self.layout.slides.len() && !self.layout.slides[self.current_slide_index].is_animating {
-                    // Animation finished, move to the next slide
-                    self.current_slide_index += 1;
+    fn slide_count(&self) -> usize {
+        self.layout.slides.len()
+    }
 
-                    // Check if that was the last slide
-                    if self.current_slide_index >= self.layout.slides.len() {
-                        self.state = SpiralState::Cleanup;
-                        // Setup cleanup index (points to the last slide that animated)
-                        self.cleanup_index = self.current_slide_index -1; // Correct index
-                        self.cleanup_timer = 0.0;
-                    } else {
-                        // More slides remain, go back to displaying the new current slide
-                        self.state = SpiralState::Displaying;
-                        self.display_timer = 0.0; // Reset display timer for the new slide
-                    }
-                }
-                // Else: Still animating, do nothing, stay in Transitioning state
-            }
-            SpiralState::Cleanup => {
-                self.cleanup_timer += dt;
-                if self.cleanup_timer >= CLEANUP_INTERVAL {
-                    // Hide the slide at cleanup_index
-                    // Check bounds just in case cleanup_index became invalid somehow
-                    if let Some(slide) = self.layout.slides.get_mut(self.cleanup_index) {
-                        slide.visible = false;
-                    } else if self.cleanup_index == 0 && self.layout.slides.len() == 1 {
-                        // Handle edge case: only one slide, hide it
-                        self.layout.slides[0].visible = false;
-                    }
+    fn slide_start(&self, index: usize) -> f32 {
+        self.starts.get(index).copied().unwrap_or(0.0)
+    }
 
-                    if self.cleanup_index > 0 {
-                        self.cleanup_index -= 1;
-                        self.cleanup_timer = 0.0;
-                    } else {
-                        // Finished hiding slide 0
-                        self.state = SpiralState::Finished;
-                    }
-                }
-            }
-            SpiralState::Finished => {
-                return false;
-            }
-        }     
+    fn slide_at(&self, time: f32) -> (usize, String) {
+        let index = index_at(&self.starts, time);
+        let state = if time >= self.total_duration() {
+            SpiralState::Finished
+        } else if time >= self.cleanup_start {
+            SpiralState::Cleanup
+        } else if time - self.slide_start(index) < self.layout.slides[index].display_duration {
+            SpiralState::Displaying
+        } else {
+            SpiralState::Transitioning
+        };
+        (index, format!("{:?}", state))
+    }
+
+    fn render_at(&self, time: f32, rl: &mut RaylibHandle, thread: &RaylibThread, framebuffer: &mut RenderTexture2D) {
+        let slides_count = self.layout.slides.len();
 
-        rl.draw_texture_mode(thread, framebuffer,  |mut tmd| {
+        rl.draw_texture_mode(thread, framebuffer, |mut tmd| {
             let mut d = tmd.begin_drawing(thread);
             d.clear_background(Color::BLACK);
 
-            // CORRECTED Drawing Logic: Draw background slides and the current active slide
-            for (i, slide) in self.layout.slides.iter().enumerate() {
-                // Draw if it's a background slide (index less than current)
-                // OR if it's the current slide being displayed or transitioning.
-                // The Cleanup/Finished states are implicitly handled because
-                // background slides (i < current_slide_index) will be drawn,
-                // and slide.draw() checks the slide.visible flag.
-                if i < self.current_slide_index ||
-                    (i == self.current_slide_index && (self.state == SpiralState::Displaying || self.state == SpiralState::Transitioning))
-                {
-                    // The slide.draw() method internally checks for slide.visible,
-                    // which handles the cleanup phase correctly.
-                    slide.draw(&mut d);
+            // Slides shown so far, the latest on top. The cleanup hides them from the last one
+            // to the first one.
+            for (i, (slide, start)) in self.layout.slides.iter().zip(self.starts.iter()).enumerate() {
+                let hidden_at = self.cleanup_start + (slides_count - i) as f32 * self.config.timing.spiral_cleanup;
+                if time < *start || time >= hidden_at {
+                    continue;
                 }
-                // Slides with index > current_slide_index are not drawn yet.
+                slide.draw(&mut d, &self.config, time - start - slide.display_duration);
             }
-        });   
 
-        return true;
+            // Caption of the slide shown prominently
+            let index = index_at(&self.starts, time);
+            let displayed = self.layout.slides.get(index)
+                .filter(|slide| time < self.cleanup_start && time - self.starts[index] < slide.display_duration);
+            if let Some(caption) = displayed.and_then(|slide| slide.caption.as_ref()) {
+                draw_caption(&mut d, &self.config, caption, 1.0);
+            }
+        });
     }
-}
\ No newline at end of file
+}
@@ -1,58 +1,74 @@
 use raylib::prelude::*;
-use crate::constants::*;
+use crate::config::RenderConfig;
 use crate::spiral::slide::Slide;
-use rand::Rng;
+use crate::slide_settings::SlideSettings;
+use crate::spiral::options::SpiralOptions;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+/// Random value between -amount and amount, 0.0 when amount is 0.0 (empty range)
+fn spread(rng: &mut StdRng, amount: f32) -> f32 {
+    if amount > 0.0 { rng.random_range(-amount..amount) } else { 0.0 }
+}
+
+/// Columns and rows of the grid holding `images_count` images with roughly square cells on an
+/// output of `display_ratio` (width / height): the longest side of the output gets more cells
+fn grid_shape(images_count: usize, display_ratio: f32) -> (i32, i32) {
+    if display_ratio >= 1.0 {
+        let grid_height = (images_count as f32 / display_ratio).sqrt().ceil().max(1.0) as i32;
+        let grid_width = (images_count as f32 / grid_height as f32).ceil() as i32;
+        (grid_width, grid_height)
+    } else {
+        let grid_width = (images_count as f32 * display_ratio).sqrt().ceil().max(1.0) as i32;
+        let grid_height = (images_count as f32 / grid_width as f32).ceil() as i32;
+        (grid_width, grid_height)
+    }
+}
 
 pub struct Layout {
     pub slides: Vec<Slide>,
+    config: RenderConfig,
+    options: SpiralOptions,
 }
 
 impl Layout {
-    pub fn new() -> Layout {
-        Layout { slides: Vec::new() }
+    pub fn new(config: RenderConfig, options: SpiralOptions) -> Layout {
+        Layout { slides: Vec::new(), config, options }
     }
 
-    pub fn add_image(&mut self, image: Texture2D) {
+    pub fn add_image(&mut self, image: Texture2D, settings: &SlideSettings) {
 
-        // Scale too big images to fit the screen
-        let initial_scale = if image.width() > image.height() {
-            if image.width() as f32 > RENDER_WIDTH as f32 * 0.9 {
-                (RENDER_WIDTH as f32 * 0.9) / image.width() as f32
-            } else {
-                1.0
-            }
-        } else {
-            if image.height() as f32 > RENDER_HEIGHT as f32 * 0.9 {
-                (RENDER_HEIGHT as f32 * 0.9) / image.height() as f32
-            } else {
-                1.0
-            }
-        };
+        // Scale too big images to fit the screen, whatever the orientation of the output
+        let initial_scale = self.config.fit_scale(image.width() as f32, image.height() as f32, 0.9);
 
         let initial_position = Vector2::new(0.5, 0.5); // Centered
         let initial_rotation = 0.0; // Rotation from EXIF is baked into the texture
+        let display_duration = settings.display_duration(self.config.timing.spiral_display);
 
         self.slides.push(Slide::new(
             image,
             initial_position,
             initial_scale,
-            initial_rotation
+            initial_rotation,
+            display_duration,
+            settings,
         ).expect("Failed to create slide"));
     }
 
     pub fn compute_layout(&mut self) {
-        let mut rng = rand::rng();
-
-        // Compute grid dimensions based on images count
-        // n * (n / display ratio) = images count
+        let mut rng = StdRng::seed_from_u64(self.config.seed);
 
-        let images_count = self.slides.len();
-        let display_ratio = RENDER_WIDTH as f32 / RENDER_HEIGHT as f32;
+        // Compute grid dimensions based on images count so that cells are roughly square
+        // whatever the output aspect ratio (landscape, portrait or square)
+        // grid_width * grid_height = images count, grid_width / grid_height = display ratio
 
-        let grid_height = (images_count as f32 / display_ratio).sqrt().ceil() as i32;
-        let grid_width = (images_count as f32 / grid_height as f32).ceil() as i32;
+        let (grid_width, grid_height) = grid_shape(self.slides.len(), self.config.aspect_ratio());
 
-        let target_width = (RENDER_WIDTH as f32 / grid_width as f32) * 1.5;
+        // Size the images after the longest side of a cell so they overlap the same way
+        // on a landscape, portrait or square canvas
+        let cell_width = self.config.width as f32 / grid_width as f32;
+        let cell_height = self.config.height as f32 / grid_height as f32;
+        let target_width = cell_width.max(cell_height) * 1.5;
         // println!("Target size: {}", target_width);
         
         let grid_step_x = 1.0 / grid_width as f32;
@@ -111,10 +127,10 @@ impl Layout {
                 _ => {}
             }
 
-            // Décalage aléatoire de +/- 1% de la position finale par rapport à la trajectoire de la spirale
+            // Décalage aléatoire de la position finale par rapport à la trajectoire de la spirale
             let end_pos_offset = Vector2::new(
-                rng.random_range(-0.01..0.01),
-                rng.random_range(-0.01..0.01)
+                spread(&mut rng, self.options.jitter),
+                spread(&mut rng, self.options.jitter)
             );
 
             // Calcule la position finale
@@ -127,9 +143,9 @@ impl Layout {
             let image_ref_dimension = slide.image.width().max(slide.image.height());
             let final_scale = target_width / image_ref_dimension as f32 * (1.0 + rng.random_range(-0.05..0.05));
      
-            let final_rotation = rng.random_range(-15.0..15.0);
+            let final_rotation = spread(&mut rng, self.options.max_rotation);
 
-            slide.set_final_position(final_position, final_scale, final_rotation);
+            slide.set_final_position(final_position, final_scale, final_rotation, self.config.timing.spiral_transition);
 
             // Update grid position based on direction
             match direction {   
@@ -141,4 +157,38 @@ impl Layout {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn landscape_grids_are_wider_than_high() {
+        assert_eq!(grid_shape(36, 16.0 / 9.0), (8, 5));
+        assert_eq!(grid_shape(10, 16.0 / 9.0), (4, 3));
+    }
+
+    #[test]
+    fn portrait_grids_are_higher_than_wide() {
+        assert_eq!(grid_shape(36, 9.0 / 16.0), (5, 8));
+        assert_eq!(grid_shape(10, 9.0 / 16.0), (3, 4));
+    }
+
+    #[test]
+    fn square_grids_are_square() {
+        assert_eq!(grid_shape(36, 1.0), (6, 6));
+        assert_eq!(grid_shape(10, 1.0), (3, 4));
+    }
+
+    #[test]
+    fn every_image_gets_a_cell() {
+        for images_count in 1..200 {
+            for display_ratio in [9.0 / 16.0, 3.0 / 4.0, 1.0, 4.0 / 3.0, 16.0 / 9.0, 21.0 / 9.0] {
+                let (grid_width, grid_height) = grid_shape(images_count, display_ratio);
+                assert!(grid_width >= 1 && grid_height >= 1);
+                assert!((grid_width * grid_height) as usize >= images_count, "{} images at {}", images_count, display_ratio);
+            }
+        }
+    }
+}
@@ -2,3 +2,4 @@ pub mod layout;
 pub mod state;
 pub mod engine;
 pub mod slide;
+pub mod options;
@@ -0,0 +1,50 @@
+use serde::Deserialize;
+use crate::engine::EngineOptionSet;
+
+/// Options of the spiral engine
+#[derive(Debug, Clone, Copy)]
+pub struct SpiralOptions {
+    pub max_rotation: f32, // photos on the wall are rotated at random by up to this angle (degrees)
+    pub jitter: f32,       // random offset of the photos on the wall, relative to the screen
+}
+
+impl Default for SpiralOptions {
+    fn default() -> Self {
+        Self { max_rotation: 15.0, jitter: 0.01 }
+    }
+}
+
+// spiral options from the command line or the [engines.spiral] section of a project file.
+#[derive(Debug, Default, Clone, Deserialize, clap::Args)]
+#[command(next_help_heading = "spiral engine")]
+#[serde(deny_unknown_fields)]
+pub struct SpiralOverrides {
+    #[arg(long = "spiral-max-rotation", value_name = "DEGREES", help = "Photos on the wall are rotated at random by up to this angle either way [default: 15]")]
+    pub max_rotation: Option<f32>,
+
+    #[arg(long = "spiral-jitter", value_name = "FRACTION", help = "Random offset of the photos on the wall, relative to the screen size [default: 0.01]")]
+    pub jitter: Option<f32>,
+}
+
+impl EngineOptionSet for SpiralOptions {
+    type Overrides = SpiralOverrides;
+
+    fn apply(&mut self, overrides: &SpiralOverrides) {
+        if let Some(max_rotation) = overrides.max_rotation {
+            self.max_rotation = max_rotation;
+        }
+        if let Some(jitter) = overrides.jitter {
+            self.jitter = jitter;
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(0.0..=180.0).contains(&self.max_rotation) {
+            return Err(format!("Invalid spiral max rotation {}: must be between 0 and 180 degrees", self.max_rotation));
+        }
+        if !(0.0..0.5).contains(&self.jitter) {
+            return Err(format!("Invalid spiral jitter {}: must be between 0.0 and 0.5", self.jitter));
+        }
+        Ok(())
+    }
+}
@@ -1,30 +1,31 @@
 use raylib::prelude::*;
-use crate::constants::*;
+use crate::config::RenderConfig;
+use crate::slide_settings::{SlideSettings, Transition};
+use crate::timeline::Track;
 
 pub struct Slide {
     pub image: Texture2D,
 
-    pub visible: bool,
+    pub display_duration: f32,
+    pub caption: Option<String>,
+    transition: Transition,
 
-    position: Vector2,
-    scale: f32,
-    rotation: f32,
-    
+    // Displayed at the center of the screen
     start_position: Vector2,
     start_scale: f32,
     start_rotation: f32,
-    
+
+    // Then moves to its place on the photo wall
     end_position: Vector2,
     end_scale: f32,
     end_rotation: f32,
-    
-    animation_timer: f32,
-    pub is_animating: bool,
-
-    tween_position_x: Option<ease::Tween>,
-    tween_position_y: Option<ease::Tween>,
-    tween_scale: Option<ease::Tween>,
-    tween_rotation: Option<ease::Tween>,
+
+    animation_duration: f32,
+
+    track_position_x: Track,
+    track_position_y: Track,
+    track_scale: Track,
+    track_rotation: Track,
 }
 
 impl Slide {
@@ -32,100 +33,98 @@ impl Slide {
         image: Texture2D, // Accept pre-loaded (and potentially rotated) texture
         initial_position: Vector2,
         initial_scale: f32,
-        initial_rotation: f32   
+        initial_rotation: f32,
+        display_duration: f32,
+        settings: &SlideSettings,
     ) -> Result<Self, String> {
         Ok(Self {
             image, // Use the passed texture
-            visible: true,
 
-            position:       initial_position,
-            scale:          initial_scale,
-            rotation:       initial_rotation,
+            display_duration,
+            caption: settings.caption.clone(),
+            transition: settings.transition(),
 
             start_position: initial_position,
             start_scale:    initial_scale,
             start_rotation: initial_rotation,
             
-            end_position:   Vector2::new(0.0, 0.0),
-            end_scale:      0.0,
-            end_rotation:   0.0,
+            end_position:   initial_position,
+            end_scale:      initial_scale,
+            end_rotation:   initial_rotation,
             
-            animation_timer: 0.0,
-            is_animating:    false,
+            animation_duration: 0.0,
 
-            tween_position_x: None,
-            tween_position_y: None,
-            tween_scale:      None,
-            tween_rotation:   None,
+            track_position_x: Track::new(ease::cubic_out, initial_position.x, initial_position.x, 0.0),
+            track_position_y: Track::new(ease::cubic_out, initial_position.y, initial_position.y, 0.0),
+            track_scale:      Track::new(ease::back_in, initial_scale, initial_scale, 0.0),
+            track_rotation:   Track::new(ease::sine_in_out, initial_rotation, initial_rotation, 0.0),
         })
     }
 
-    pub fn set_final_position(&mut self, final_position: Vector2, final_scale: f32, final_rotation: f32) {
+    pub fn set_final_position(&mut self, final_position: Vector2, final_scale: f32, final_rotation: f32, duration: f32) {
         self.end_position = final_position;
         self.end_scale = final_scale;
         self.end_rotation = final_rotation;
+        self.animation_duration = duration;
         
-        self.tween_position_x = Some(ease::Tween::new(ease::cubic_out, self.start_position.x, final_position.x, ANIMATION_DURATION));
-        self.tween_position_y = Some(ease::Tween::new(ease::cubic_out, self.start_position.y, final_position.y, ANIMATION_DURATION));
-        self.tween_scale      = Some(ease::Tween::new(ease::back_in, self.start_scale, final_scale, ANIMATION_DURATION));
-        self.tween_rotation   = Some(ease::Tween::new(ease::sine_in_out, self.start_rotation, final_rotation, ANIMATION_DURATION));
-    }
-
-    pub fn start_background_animation(&mut self) {
-        if !self.is_animating {
-            self.start_position = self.position;
-            self.start_scale = self.scale;
-            self.start_rotation = self.rotation;
-            self.animation_timer = 0.0;
-            self.is_animating = true;
-        }
+        self.track_position_x = Track::new(ease::cubic_out, self.start_position.x, final_position.x, duration);
+        self.track_position_y = Track::new(ease::cubic_out, self.start_position.y, final_position.y, duration);
+        self.track_scale      = Track::new(ease::back_in, self.start_scale, final_scale, duration);
+        self.track_rotation   = Track::new(ease::sine_in_out, self.start_rotation, final_rotation, duration);
     }
 
-    pub fn update(&mut self, dt: f32) {
-        if !self.is_animating {
-            return;
-        }
-
-        self.position.x = self.tween_position_x.as_mut().expect("Tween should be initialized").apply(dt);
-        self.position.y = self.tween_position_y.as_mut().expect("Tween should be initialized").apply(dt);
-        self.scale      = self.tween_scale.as_mut().expect("Tween should be initialized").apply(dt);
-        self.rotation   = self.tween_rotation.as_mut().expect("Tween should be initialized").apply(dt);
-        
-        self.animation_timer += dt;
-        if self.animation_timer >= ANIMATION_DURATION {
-            self.is_animating = false;
-            self.position     = self.end_position;
-            self.scale        = self.end_scale;
-            self.rotation     = self.end_rotation;
+    /// Time taken to move to the photo wall
+    pub fn transition_duration(&self) -> f32 {
+        match self.transition {
+            Transition::Cut => 0.0,
+            _ => self.animation_duration,
         }
     }
 
-    pub fn draw(&self, d: &mut RaylibDrawHandle) {
-        if self.visible {
-            let screen_width = RENDER_WIDTH as f32;
-            let screen_height = RENDER_HEIGHT as f32;
-
-            let tex_width = self.image.width() as f32;
-            let tex_height = self.image.height() as f32;
-
-            let scaled_width = tex_width * self.scale;
-            let scaled_height = tex_height * self.scale;
-
-            let draw_pos = Vector2::new(
-                screen_width * self.position.x - scaled_width * 0.5,
-                screen_height * self.position.y - scaled_height * 0.5,
-            );
-
-            let origin = Vector2::new(scaled_width / 2.0, scaled_height / 2.0);
-
-            d.draw_texture_pro(
-                &self.image,
-                Rectangle::new(0.0, 0.0, tex_width, tex_height), // Source rect uses original texture size
-                Rectangle::new(draw_pos.x + origin.x, draw_pos.y + origin.y, scaled_width, scaled_height), // Dest rect uses scaled size
-                origin,
-                self.rotation,
-                Color::WHITE,
-            );
-        }
+    /// Draws the slide `time` seconds after it started moving to the wall
+    /// (negative while it is displayed at the center of the screen)
+    pub fn draw(&self, d: &mut RaylibDrawHandle, config: &RenderConfig, time: f32) {
+        let (position, scale, rotation, alpha) = if time < 0.0 {
+            (self.start_position, self.start_scale, self.start_rotation, 1.0)
+        } else if time >= self.transition_duration() {
+            (self.end_position, self.end_scale, self.end_rotation, 1.0)
+        } else {
+            match self.transition {
+                Transition::Push => (
+                    Vector2::new(self.track_position_x.at(time), self.track_position_y.at(time)),
+                    self.track_scale.at(time),
+                    self.track_rotation.at(time),
+                    1.0,
+                ),
+                // Appear directly on the wall, fading in
+                Transition::Fade => (self.end_position, self.end_scale, self.end_rotation, time / self.animation_duration),
+                Transition::Cut => (self.end_position, self.end_scale, self.end_rotation, 1.0),
+            }
+        };
+
+        let screen_width = config.width as f32;
+        let screen_height = config.height as f32;
+
+        let tex_width = self.image.width() as f32;
+        let tex_height = self.image.height() as f32;
+
+        let scaled_width = tex_width * scale;
+        let scaled_height = tex_height * scale;
+
+        let draw_pos = Vector2::new(
+            screen_width * position.x - scaled_width * 0.5,
+            screen_height * position.y - scaled_height * 0.5,
+        );
+
+        let origin = Vector2::new(scaled_width / 2.0, scaled_height / 2.0);
+
+        d.draw_texture_pro(
+            &self.image,
+            Rectangle::new(0.0, 0.0, tex_width, tex_height), // Source rect uses original texture size
+            Rectangle::new(draw_pos.x + origin.x, draw_pos.y + origin.y, scaled_width, scaled_height), // Dest rect uses scaled size
+            origin,
+            rotation,
+            Color::new(255, 255, 255, (255.0 * alpha.clamp(0.0, 1.0)) as u8),
+        );
     }
-}
\ No newline at end of file
+}
@@ -61,7 +61,6 @@ impl DetectionModel {
 
         for xs in &dl {
             let ys = self.model.forward(&xs)?;
-            println!("ys: {:?}", ys);
 
             for y in ys.iter() {
                 if let Some(hbbs) = &y.hbbs() {
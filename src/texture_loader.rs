@@ -3,60 +3,70 @@ use std::io::Cursor;
 use std::path::Path;
 use raylib::prelude::*;
 use exif::{Reader, Tag, Value, In};
+use crate::image_format::ImageFormat;
 
-// --- Helper: Load and Sort Image Paths ---
-pub fn load_sorted_image_paths(dir_path: &str) -> Result<Vec<std::path::PathBuf>, String> {
-    let mut paths = Vec::new();
-    let entries = fs::read_dir(dir_path)
-        .map_err(|e| format!("Failed to read directory {}: {}", dir_path, e))?;
-
-    for entry in entries {
-        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
-        let path = entry.path();
-        if path.is_file() {
-            if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
-                match ext.to_lowercase().as_str() {
-                    "png" | "jpg" | "jpeg" | "bmp" | "gif" => {
-                        paths.push(path);
-                    }
-                    _ => {}
-                }
-            }
+/// Turns the image upright according to its EXIF orientation, whose values describe how the
+/// stored pixels relate to the scene:
+/// 1 = Top-left (Normal)
+/// 2 = Top-right (mirrored horizontally)
+/// 3 = Bottom-right (180 deg)
+/// 4 = Bottom-left (mirrored vertically)
+/// 5 = Left-top (mirrored along the top-left to bottom-right diagonal)
+/// 6 = Right-top (90 deg clockwise)
+/// 7 = Right-bottom (mirrored along the top-right to bottom-left diagonal)
+/// 8 = Left-bottom (270 deg clockwise / 90 deg counter-clockwise)
+fn apply_exif_orientation(image: &mut Image, orientation: u16) {
+    match orientation {
+        2 => image.flip_horizontal(),
+        3 => {
+            image.rotate_cw();
+            image.rotate_cw(); // 180 deg
         }
-    }
-    paths.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
-    if paths.is_empty() {
-        Err(format!("No image files found in directory: {}", dir_path))
-    } else {
-        Ok(paths)
+        4 => image.flip_vertical(),
+        5 => {
+            // Transpose
+            image.rotate_cw();
+            image.flip_horizontal();
+        }
+        6 => image.rotate_cw(), // 90 deg clockwise
+        7 => {
+            // Transverse
+            image.rotate_ccw();
+            image.flip_horizontal();
+        }
+        8 => image.rotate_ccw(), // 90 deg counter-clockwise
+        _ => { /* No change needed for 1 or invalid values */ }
     }
 }
 
-// --- Load Image, Apply EXIF Rotation, Create Texture ---
-pub fn load_texture_with_exif_rotation(
-    rl: &mut RaylibHandle,
-    thread: &RaylibThread,
-    image_path: &Path,
-) -> Result<Texture2D, String> {
+/// Loads an image and turns it upright according to its EXIF orientation
+pub fn load_upright_image(image_path: &Path) -> Result<Image, String> {
     let file_bytes = fs::read(image_path)
         .map_err(|e| format!("Failed to read file {:?}: {}", image_path, e))?;
 
+    // The container is recognized from its content, a PNG named `.jpg` loads as a PNG.
+    // Unknown files are left to raylib with their extension as hint.
+    let format = ImageFormat::detect(&file_bytes);
+    if let Some(format) = format.filter(|format| !format.is_decodable()) {
+        return Err(format!("Failed to load image data for {:?}: {} images are not supported", image_path, format.name()));
+    }
+
     let mut orientation = 1; // Default: no rotation
 
-    // Attempt to read EXIF data (only works reliably for JPEG)
-    let extension = image_path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
-    if extension == "jpg" || extension == "jpeg" {
+    // Attempt to read EXIF data, from any container that may hold some
+    if format.is_some_and(|format| format.has_exif()) {
         match Reader::new().read_from_container(&mut Cursor::new(&file_bytes)) {
             Ok(exif) => {
                 if let Some(field) = exif.get_field(Tag::Orientation, In::PRIMARY) {
                     if let Value::Short(values) = &field.value {
                         if !values.is_empty() {
                             orientation = values[0];
-                            // println!("Image {:?} EXIF Orientation: {}", image_path.file_name().unwrap(), orientation); // Debug
                         }
                     }
                 }
             }
+            // Photos without EXIF data are common outside of JPEG, they are not worth a warning
+            Err(exif::Error::NotFound(_)) => {}
             Err(e) => {
                 // Log non-critical error: EXIF reading failed, proceed without rotation
                 eprintln!("Warning: Could not read EXIF data for {:?}: {}", image_path.file_name().unwrap_or_else(|| image_path.as_os_str()), e);
@@ -65,34 +75,27 @@ pub fn load_texture_with_exif_rotation(
     }
 
     // Load image data into memory (Image struct)
-    // Provide extension hint for loading from memory
-    let mut image = Image::load_image_from_mem(&(".".to_string() + &extension), &file_bytes)
+    // Provide file type hint for loading from memory
+    let extension = match format {
+        Some(format) => format.extension().to_string(),
+        None => ".".to_string() + &image_path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase(),
+    };
+    let mut image = Image::load_image_from_mem(&extension, &file_bytes)
         .map_err(|e| format!("Failed to load image data for {:?}: {}", image_path, e))?; // Use map_err for RaylibError
 
-    // Apply rotation based on orientation value
-    // 1 = Top-left (Normal)
-    // 3 = Bottom-right (180 deg)
-    // 6 = Top-right (90 deg clockwise)
-    // 8 = Bottom-left (270 deg clockwise / 90 deg counter-clockwise)
-    // Others involve flips, ignored for simplicity here.
-    match orientation {
-        3 => {
-            image.rotate_cw();
-            image.rotate_cw(); // 180 deg
-            println!("Applied 180 deg rotation"); // Debug
-        }
-        6 => {
-            image.rotate_cw(); // 90 deg clockwise
-            println!("Applied 90 deg CW rotation"); // Debug
-        }
-        8 => {
-            image.rotate_ccw(); // 90 deg counter-clockwise
-            println!("Applied 90 deg CCW rotation"); // Debug
-        }
-        _ => { /* No rotation needed for 1 or others */ }
-    }
+    apply_exif_orientation(&mut image, orientation);
+    Ok(image)
+}
 
-    // Create Texture2D from the potentially rotated Image data
+// --- Load Image, Apply EXIF Rotation, Create Texture ---
+pub fn load_texture_with_exif_rotation(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    image_path: &Path,
+) -> Result<Texture2D, String> {
+    let image = load_upright_image(image_path)?;
+
+    // Create Texture2D from the upright Image data
     let texture = rl.load_texture_from_image(thread, &image)
         .map_err(|e| format!("Failed to create texture for {:?}: {}", image_path, e))?; // Use map_err
 
@@ -100,4 +103,33 @@ pub fn load_texture_with_exif_rotation(
     drop(image);
 
     Ok(texture)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gray levels of the 3x2 blocks of 16 pixels of the upright fixtures, left to right and
+    /// top to bottom. Every fixture stores them as its EXIF orientation describes.
+    const BLOCKS: [u8; 6] = [0, 50, 100, 150, 200, 250];
+
+    fn fixture(orientation: u16) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(format!("orientation-{}.jpg", orientation))
+    }
+
+    #[test]
+    fn every_exif_orientation_is_turned_upright() {
+        for orientation in 1..=8 {
+            let image = load_upright_image(&fixture(orientation)).unwrap();
+            assert_eq!((image.width(), image.height()), (48, 32), "orientation {}", orientation);
+            for (i, level) in BLOCKS.iter().enumerate() {
+                let (x, y) = (i as i32 % 3 * 16 + 8, i as i32 / 3 * 16 + 8);
+                let color = image.get_color(x, y);
+                assert!(
+                    color.r.abs_diff(*level) <= 20,
+                    "orientation {}: block {} is {} instead of {}", orientation, i, color.r, level,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+/// Part of the slideshow to render, `--range 10s..20s`. The end is the end of the slideshow
+/// when omitted (`--range 1:30..`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeRange {
+    pub start: f32,
+    pub end: Option<f32>,
+}
+
+/// Parses a time in the slideshow: `83.5`, `83.5s`, `01:23.5` or `00:01:23.5`
+pub fn parse_time(text: &str) -> Result<f32, String> {
+    let invalid = || format!("Invalid time '{}' (expected 83.5, 83.5s, MM:SS.s or HH:MM:SS.s)", text);
+
+    let trimmed = text.trim();
+    let trimmed = trimmed.strip_suffix('s').unwrap_or(trimmed);
+    let parts: Vec<&str> = trimmed.split(':').collect();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+
+    let mut seconds = 0.0;
+    for (i, part) in parts.iter().enumerate() {
+        // Hours and minutes are whole numbers, only seconds have decimals
+        let is_last = i == parts.len() - 1;
+        if !is_decimal(part, is_last) {
+            return Err(invalid());
+        }
+        let value: f32 = part.parse().map_err(|_| invalid())?;
+        // Minutes and seconds are below 60 after a ':'
+        if i > 0 && value >= 60.0 {
+            return Err(invalid());
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Ok(seconds)
+}
+
+/// Digits, with a decimal part if `fraction` is allowed: no sign, exponent, `inf` or `NaN`
+fn is_decimal(text: &str, fraction: bool) -> bool {
+    let (whole, decimals) = match text.split_once('.') {
+        Some((whole, decimals)) if fraction => (whole, decimals),
+        Some(_) => return false,
+        None => (text, "0"),
+    };
+    let digits = |text: &str| !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit());
+    digits(whole) && digits(decimals)
+}
+
+/// Parses `START..END`, either end may be omitted
+pub fn parse_range(text: &str) -> Result<TimeRange, String> {
+    let (start, end) = text.split_once("..")
+        .ok_or_else(|| format!("Invalid range '{}' (expected START..END, e.g. 10s..20s)", text))?;
+
+    let start = if start.trim().is_empty() { 0.0 } else { parse_time(start)? };
+    let end = if end.trim().is_empty() { None } else { Some(parse_time(end)?) };
+    if end.is_some_and(|end| end <= start) {
+        return Err(format!("Invalid range '{}': the end must be after the start", text));
+    }
+    Ok(TimeRange { start, end })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_are_parsed() {
+        assert_eq!(parse_time("83.5"), Ok(83.5));
+        assert_eq!(parse_time("83.5s"), Ok(83.5));
+        assert_eq!(parse_time("90"), Ok(90.0));
+        assert_eq!(parse_time("01:23.5"), Ok(83.5));
+        assert_eq!(parse_time("1:30"), Ok(90.0));
+        assert_eq!(parse_time("00:01:23.5"), Ok(83.5));
+        assert_eq!(parse_time("2:00:00"), Ok(7200.0));
+        assert_eq!(parse_time(" 12 "), Ok(12.0));
+    }
+
+    #[test]
+    fn invalid_times_are_rejected() {
+        for text in ["", "s", "1e3", "inf", "NaN", "-5", "+5", "1.5:00", "1:60", "1:5:60", "1:2:3:4", "1:", ":30", ".5", "5.", "1.2.3", "1:30s5"] {
+            assert!(parse_time(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn ranges_are_parsed() {
+        assert_eq!(parse_range("10s..20s"), Ok(TimeRange { start: 10.0, end: Some(20.0) }));
+        assert_eq!(parse_range("1:30.."), Ok(TimeRange { start: 90.0, end: None }));
+        assert_eq!(parse_range("..20"), Ok(TimeRange { start: 0.0, end: Some(20.0) }));
+        assert_eq!(parse_range(".."), Ok(TimeRange { start: 0.0, end: None }));
+    }
+
+    #[test]
+    fn invalid_ranges_are_rejected() {
+        assert!(parse_range("10s").is_err());
+        assert!(parse_range("20..10").is_err());
+        assert!(parse_range("10..10").is_err());
+        assert!(parse_range("10..1e3").is_err());
+        assert!(parse_range("abc..20").is_err());
+    }
+}
@@ -0,0 +1,123 @@
+/// Easing function from raylib::ease: (time, start value, change, duration) -> value
+pub type EaseFn = fn(f32, f32, f32, f32) -> f32;
+
+/// A value easing from `from` to `to` over `duration` seconds. Unlike `ease::Tween` it has no
+/// state: it can be evaluated at any time, in any order, which makes frames seekable.
+#[derive(Clone, Copy)]
+pub struct Track {
+    ease: EaseFn,
+    from: f32,
+    to: f32,
+    duration: f32,
+}
+
+impl Track {
+    pub fn new(ease: EaseFn, from: f32, to: f32, duration: f32) -> Self {
+        Self { ease, from, to, duration }
+    }
+
+    /// Value `time` seconds after the start of the track, `from` before and `to` after it
+    pub fn at(&self, time: f32) -> f32 {
+        if self.duration <= 0.0 || time >= self.duration {
+            self.to
+        } else if time <= 0.0 {
+            self.from
+        } else {
+            (self.ease)(time, self.from, self.to - self.from, self.duration)
+        }
+    }
+}
+
+/// Start times of things happening one after the other: `[0, d0, d0 + d1, ...]`
+pub fn start_times(durations: impl Iterator<Item = f32>) -> Vec<f32> {
+    let mut start = 0.0;
+    durations
+        .map(|duration| {
+            let time = start;
+            start += duration;
+            time
+        })
+        .collect()
+}
+
+/// Index of the last thing started at `time`, given sorted start times. 0 before the first
+/// start, and when there is nothing.
+pub fn index_at(starts: &[f32], time: f32) -> usize {
+    starts.partition_point(|start| *start <= time).saturating_sub(1)
+}
+
+/// Finds the phase running `time` seconds after the first one started, among phases laid
+/// end to end, with the time spent in it. None before the first phase and after the last one.
+pub fn phase_at<P: Copy>(phases: &[(P, f32)], time: f32) -> Option<(P, f32)> {
+    if time < 0.0 {
+        return None;
+    }
+    let mut start = 0.0;
+    for (phase, duration) in phases {
+        if time < start + duration {
+            return Some((*phase, time - start));
+        }
+        start += duration;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linear(time: f32, from: f32, change: f32, duration: f32) -> f32 {
+        from + change * time / duration
+    }
+
+    #[test]
+    fn tracks_hold_their_ends_outside_their_duration() {
+        let track = Track::new(linear, 10.0, 20.0, 2.0);
+        assert_eq!(track.at(-1.0), 10.0);
+        assert_eq!(track.at(0.0), 10.0);
+        assert_eq!(track.at(1.0), 15.0);
+        assert_eq!(track.at(2.0), 20.0);
+        assert_eq!(track.at(5.0), 20.0);
+    }
+
+    #[test]
+    fn zero_length_tracks_are_at_their_end() {
+        let track = Track::new(linear, 10.0, 20.0, 0.0);
+        assert_eq!(track.at(-1.0), 20.0);
+        assert_eq!(track.at(0.0), 20.0);
+        assert_eq!(track.at(1.0), 20.0);
+    }
+
+    #[test]
+    fn start_times_add_up_durations() {
+        assert_eq!(start_times([1.0, 2.0, 0.0, 3.0].into_iter()), vec![0.0, 1.0, 3.0, 3.0]);
+        assert_eq!(start_times(std::iter::empty()), Vec::<f32>::new());
+    }
+
+    #[test]
+    fn index_at_finds_the_last_started() {
+        let starts = [0.0, 1.0, 3.0, 3.0];
+        assert_eq!(index_at(&starts, 0.0), 0);
+        assert_eq!(index_at(&starts, 0.5), 0);
+        assert_eq!(index_at(&starts, 1.0), 1);
+        assert_eq!(index_at(&starts, 2.9), 1);
+        // Of two things starting together, the second one hides the first
+        assert_eq!(index_at(&starts, 3.0), 3);
+        assert_eq!(index_at(&starts, 100.0), 3);
+        assert_eq!(index_at(&starts, -1.0), 0);
+        assert_eq!(index_at(&[], 1.0), 0);
+    }
+
+    #[test]
+    fn phase_at_finds_the_running_phase() {
+        let phases = [('a', 1.0), ('b', 0.0), ('c', 2.0)];
+        assert_eq!(phase_at(&phases, -0.1), None);
+        assert_eq!(phase_at(&phases, 0.0), Some(('a', 0.0)));
+        assert_eq!(phase_at(&phases, 0.5), Some(('a', 0.5)));
+        // Zero-length phases never run, the next one starts at once
+        assert_eq!(phase_at(&phases, 1.0), Some(('c', 0.0)));
+        assert_eq!(phase_at(&phases, 2.5), Some(('c', 1.5)));
+        assert_eq!(phase_at(&phases, 3.0), None);
+        assert_eq!(phase_at::<char>(&[], 0.0), None);
+    }
+}
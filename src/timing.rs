@@ -0,0 +1,234 @@
+use std::path::Path;
+use serde::Deserialize;
+use crate::constants::*;
+
+/// Durations (seconds) of every phase of both engines.
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+    // push-box
+    pub entering: f32,
+    pub zooming_in: f32,
+    pub displaying: f32,
+    pub zooming_out: f32,
+    pub exiting: f32,
+
+    // spiral
+    pub spiral_display: f32,
+    pub spiral_transition: f32,
+    pub spiral_cleanup: f32,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            entering:    DEFAULT_ANIMATION_DURATION,
+            zooming_in:  DEFAULT_ANIMATION_DURATION,
+            displaying:  DEFAULT_DISPLAY_DURATION,
+            zooming_out: DEFAULT_ANIMATION_DURATION,
+            exiting:     DEFAULT_ANIMATION_DURATION,
+
+            spiral_display:    DEFAULT_DISPLAY_DURATION,
+            spiral_transition: DEFAULT_ANIMATION_DURATION,
+            spiral_cleanup:    DEFAULT_CLEANUP_INTERVAL,
+        }
+    }
+}
+
+pub const TIMING_PRESETS: &[&str] = &["default", "memorial", "recap"];
+
+impl Timing {
+    pub fn preset(name: &str) -> Result<Self, String> {
+        match name {
+            "default" => Ok(Self::default()),
+            // Slow pacing: long display, soft transitions
+            "memorial" => Ok(Self {
+                entering:    1.2,
+                zooming_in:  1.0,
+                displaying:  6.0,
+                zooming_out: 1.0,
+                exiting:     1.2,
+
+                spiral_display:    5.0,
+                spiral_transition: 1.2,
+                spiral_cleanup:    0.4,
+            }),
+            // Fast pacing: short display, snappy transitions
+            "recap" => Ok(Self {
+                entering:    0.3,
+                zooming_in:  0.25,
+                displaying:  1.0,
+                zooming_out: 0.25,
+                exiting:     0.3,
+
+                spiral_display:    0.8,
+                spiral_transition: 0.3,
+                spiral_cleanup:    0.08,
+            }),
+            _ => Err(format!("Unknown timing preset '{}' (expected one of: {})", name, TIMING_PRESETS.join(", "))),
+        }
+    }
+
+    /// Applies the durations set in `overrides` on top of this profile
+    pub fn apply(&mut self, overrides: &TimingOverrides) {
+        let fields = [
+            (&mut self.entering,          overrides.entering),
+            (&mut self.zooming_in,        overrides.zooming_in),
+            (&mut self.displaying,        overrides.displaying),
+            (&mut self.zooming_out,       overrides.zooming_out),
+            (&mut self.exiting,           overrides.exiting),
+            (&mut self.spiral_display,    overrides.spiral_display),
+            (&mut self.spiral_transition, overrides.spiral_transition),
+            (&mut self.spiral_cleanup,    overrides.spiral_cleanup),
+        ];
+        for (field, value) in fields {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let fields = [
+            ("entering",          self.entering),
+            ("zooming_in",        self.zooming_in),
+            ("displaying",        self.displaying),
+            ("zooming_out",       self.zooming_out),
+            ("exiting",           self.exiting),
+            ("spiral_display",    self.spiral_display),
+            ("spiral_transition", self.spiral_transition),
+            ("spiral_cleanup",    self.spiral_cleanup),
+        ];
+        for (name, value) in fields {
+            if !value.is_finite() || value <= 0.0 {
+                return Err(format!("Invalid duration for '{}': {} (must be a positive number of seconds)", name, value));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Partial timing profile, as read from the command line or a TOML timing file.
+// Unset durations keep the value of the selected preset.
+// (plain comment: a doc comment would replace the command description in --help)
+#[derive(Debug, Default, Clone, Deserialize, clap::Args)]
+#[command(next_help_heading = "Timing")]
+#[serde(deny_unknown_fields)]
+pub struct TimingOverrides {
+    #[arg(id = "timing_preset", long = "timing", value_name = "PRESET", help = "Timing preset (default, memorial or recap)")]
+    pub preset: Option<String>,
+
+    #[arg(long = "entering-duration", value_name = "SECONDS", help = "push-box: duration of the slide entering the screen")]
+    pub entering: Option<f32>,
+
+    #[arg(long = "zoom-in-duration", value_name = "SECONDS", help = "push-box: duration of the zoom in")]
+    pub zooming_in: Option<f32>,
+
+    #[arg(long = "display-duration", value_name = "SECONDS", help = "push-box: duration each slide is displayed full screen")]
+    pub displaying: Option<f32>,
+
+    #[arg(long = "zoom-out-duration", value_name = "SECONDS", help = "push-box: duration of the zoom out")]
+    pub zooming_out: Option<f32>,
+
+    #[arg(long = "exiting-duration", value_name = "SECONDS", help = "push-box: duration of the slide leaving the screen")]
+    pub exiting: Option<f32>,
+
+    #[arg(long = "spiral-display-duration", value_name = "SECONDS", help = "spiral: duration each slide is shown prominently")]
+    pub spiral_display: Option<f32>,
+
+    #[arg(long = "spiral-transition-duration", value_name = "SECONDS", help = "spiral: duration of the move to the photo wall")]
+    pub spiral_transition: Option<f32>,
+
+    #[arg(long = "spiral-cleanup-interval", value_name = "SECONDS", help = "spiral: time between photos disappearing at the end")]
+    pub spiral_cleanup: Option<f32>,
+}
+
+impl TimingOverrides {
+    /// Reads a TOML timing file, e.g.
+    ///
+    /// ```toml
+    /// preset = "memorial"
+    /// displaying = 8.0
+    /// ```
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read timing file {}: {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| format!("Invalid timing file {}: {}", path.display(), e))
+    }
+}
+
+/// Builds the final timing profile: preset, then timing files (in order), then command line values.
+pub fn resolve_timing(files: &[&TimingOverrides], cli: &TimingOverrides) -> Result<Timing, String> {
+    let preset = cli.preset.as_deref()
+        .or(files.iter().rev().find_map(|f| f.preset.as_deref()))
+        .unwrap_or("default");
+
+    let mut timing = Timing::preset(preset)?;
+    for file in files {
+        timing.apply(file);
+    }
+    timing.apply(cli);
+    timing.validate()?;
+    Ok(timing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(toml: &str) -> TimingOverrides {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn the_default_preset_applies_without_overrides() {
+        let timing = resolve_timing(&[], &TimingOverrides::default()).unwrap();
+        assert_eq!(timing.displaying, DEFAULT_DISPLAY_DURATION);
+        assert_eq!(timing.spiral_cleanup, DEFAULT_CLEANUP_INTERVAL);
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let project = overrides("preset = \"memorial\"\ndisplaying = 8.0\nexiting = 2.0");
+        let file = overrides("displaying = 9.0\nentering = 3.0");
+        let cli = TimingOverrides { entering: Some(4.0), ..Default::default() };
+
+        let timing = resolve_timing(&[&project, &file], &cli).unwrap();
+        assert_eq!(timing.displaying, 9.0, "timing file over project");
+        assert_eq!(timing.entering, 4.0, "command line over timing file");
+        assert_eq!(timing.exiting, 2.0, "project over preset");
+        assert_eq!(timing.zooming_in, 1.0, "memorial preset");
+    }
+
+    #[test]
+    fn the_last_preset_set_wins() {
+        let project = overrides("preset = \"memorial\"");
+        let file = overrides("preset = \"recap\"");
+        assert_eq!(resolve_timing(&[&project, &file], &TimingOverrides::default()).unwrap().displaying, 1.0);
+
+        let cli = TimingOverrides { preset: Some("default".to_string()), ..Default::default() };
+        assert_eq!(resolve_timing(&[&project, &file], &cli).unwrap().displaying, DEFAULT_DISPLAY_DURATION);
+    }
+
+    #[test]
+    fn a_preset_keeps_the_durations_of_earlier_layers() {
+        // Presets are picked first and every layer applies over them, whichever layer names it
+        let project = overrides("displaying = 8.0");
+        let cli = TimingOverrides { preset: Some("recap".to_string()), ..Default::default() };
+        let timing = resolve_timing(&[&project], &cli).unwrap();
+        assert_eq!(timing.displaying, 8.0);
+        assert_eq!(timing.entering, 0.3);
+    }
+
+    #[test]
+    fn invalid_timings_are_rejected() {
+        let cli = TimingOverrides { preset: Some("slow".to_string()), ..Default::default() };
+        assert!(resolve_timing(&[], &cli).is_err());
+
+        let file = overrides("displaying = 0.0");
+        assert!(resolve_timing(&[&file], &TimingOverrides::default()).is_err());
+        let cli = TimingOverrides { exiting: Some(-1.0), ..Default::default() };
+        assert!(resolve_timing(&[], &cli).is_err());
+        assert!(toml::from_str::<TimingOverrides>("display = 2.0").is_err(), "unknown keys");
+    }
+}
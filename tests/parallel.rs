@@ -0,0 +1,70 @@
+//! Renders the same slideshow with one and with several worker processes, with the default
+//! lossy codec, and compares the decoded frames. Needs ffmpeg and an OpenGL context (e.g. under
+//! Xvfb), run it with `cargo test -- --ignored`.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use raylib::prelude::*;
+
+const COLORS: [Color; 5] = [Color::RED, Color::GREEN, Color::BLUE, Color::YELLOW, Color::PURPLE];
+
+// Photos shown long enough for the video to span several segments of 30 seconds
+const PHOTOS: usize = 12;
+const DISPLAY_DURATION: &str = "8";
+
+fn test_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("slideshow-test-{}-parallel", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("photos")).unwrap();
+    dir
+}
+
+/// Renders the photos of `dir` with `jobs` processes and returns the MD5 of every decoded frame
+fn frame_hashes(dir: &Path, jobs: usize) -> Vec<String> {
+    let output = dir.join(format!("jobs-{}.mp4", jobs));
+    let status = Command::new(env!("CARGO_BIN_EXE_slideshow"))
+        .args(["-e", "spiral", "--headless", "--seed", "1", "--width", "320", "--height", "180", "--fps", "10"])
+        .args(["--spiral-display-duration", DISPLAY_DURATION])
+        .args(["--force", "--jobs", &jobs.to_string()])
+        .arg("-d").arg(dir.join("photos"))
+        .arg("-o").arg(&output)
+        .status()
+        .expect("Failed to run slideshow");
+    assert!(status.success(), "slideshow --jobs {} failed ({})", jobs, status);
+
+    let framemd5 = Command::new("ffmpeg")
+        .args(["-v", "error", "-i"]).arg(&output)
+        .args(["-f", "framemd5", "-"])
+        .output()
+        .expect("Failed to run ffmpeg");
+    assert!(framemd5.status.success(), "ffmpeg failed: {}", String::from_utf8_lossy(&framemd5.stderr));
+
+    // `stream, dts, pts, duration, size, hash` per frame, after `#` comment lines
+    String::from_utf8_lossy(&framemd5.stdout)
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.rsplit(',').next().map(|hash| hash.trim().to_string()))
+        .collect()
+}
+
+#[test]
+#[ignore = "needs ffmpeg and an OpenGL context"]
+fn workers_render_the_frames_of_a_single_process() {
+    let dir = test_dir();
+    for i in 0..PHOTOS {
+        let image = Image::gen_image_color(64 + 16 * i as i32, 48, COLORS[i % COLORS.len()]);
+        let path = dir.join("photos").join(format!("{}.png", i));
+        assert!(image.export_image(&path.to_string_lossy()), "Failed to write {}", path.display());
+    }
+
+    let single = frame_hashes(&dir, 1);
+    let parallel = frame_hashes(&dir, 3);
+    // 30 seconds at 10 fps per segment
+    assert!(single.len() > 2 * 300, "{} frames, the video should span several segments", single.len());
+    assert_eq!(single.len(), parallel.len(), "frame count");
+    for (frame, (single, parallel)) in single.iter().zip(parallel.iter()).enumerate() {
+        assert_eq!(single, parallel, "frame {}", frame);
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}